@@ -1,8 +1,20 @@
 module large::drop;
 
+use sui::dynamic_field as df;
 use sui::hash;
 use sui::{coin, table::Table};
 
+/// Plain Blake2b-256 leaves and nodes.
+const TREE_V1: u8 = 1;
+/// Leaves prefixed with `LEAF_TAG` and nodes with `NODE_TAG`, so a node can't
+/// be passed off as a leaf. Mirrors `merkle::TreeVersion` in the CLI.
+const TREE_V2: u8 = 2;
+const LEAF_TAG: u8 = 0;
+const NODE_TAG: u8 = 1;
+/// Dynamic field of drops created with `create_drop_v2`, kept out of the
+/// struct so older drops are unchanged. Drops without it are v1.
+const TREE_VERSION_KEY: vector<u8> = b"tree_version";
+
 public struct Drop<phantom TOKEN> has key, store {
     id: UID,
     root: vector<u8>,
@@ -11,7 +23,7 @@ public struct Drop<phantom TOKEN> has key, store {
     vault: sui::balance::Balance<TOKEN>,
     // Walrus object - addresses + allocations
     allocations: address,
-    // Walrus object - tree
+    // Walrus object - tree, or @0x0 if claimers rebuild it from the allocations
     merkle_tree: address,
     registry: Table<address, bool>,
 }
@@ -28,12 +40,39 @@ public fun create_drop<TOKEN>(
     merkle_storage: address,
     wallet_count: u32,
     ctx: &mut TxContext,
+): DeleteCap {
+    new_drop(root, funds, leaves_storage, merkle_storage, wallet_count, TREE_V1, ctx)
+}
+
+/// Like `create_drop`, for a tree built with domain-separated hashing.
+public fun create_drop_v2<TOKEN>(
+    root: vector<u8>,
+    funds: coin::Coin<TOKEN>,
+    leaves_storage: address,
+    merkle_storage: address,
+    wallet_count: u32,
+    ctx: &mut TxContext,
+): DeleteCap {
+    new_drop(root, funds, leaves_storage, merkle_storage, wallet_count, TREE_V2, ctx)
+}
+
+fun new_drop<TOKEN>(
+    root: vector<u8>,
+    funds: coin::Coin<TOKEN>,
+    leaves_storage: address,
+    merkle_storage: address,
+    wallet_count: u32,
+    tree_version: u8,
+    ctx: &mut TxContext,
 ): DeleteCap {
     assert!(wallet_count >= 2);
     assert!(root.length() == 32);
     assert!(funds.value() > 0);
 
-    let id = object::new(ctx);
+    let mut id = object::new(ctx);
+    if (tree_version != TREE_V1) {
+        df::add(&mut id, TREE_VERSION_KEY, tree_version);
+    };
     let admin_cap = DeleteCap {
         id: object::new(ctx),
         object_id: object::uid_to_inner(&id),
@@ -61,7 +100,7 @@ public fun destroy_drop<TOKEN>(
     let DeleteCap { id: cap_id, object_id: _ } = cap;
     object::delete(cap_id);
     let Drop {
-        id,
+        mut id,
         vault,
         registry,
         root: _,
@@ -71,6 +110,9 @@ public fun destroy_drop<TOKEN>(
         merkle_tree: _,
     } = drop;
     registry.drop();
+    if (df::exists_(&id, TREE_VERSION_KEY)) {
+        let _: u8 = df::remove(&mut id, TREE_VERSION_KEY);
+    };
     object::delete(id);
     coin::from_balance(vault, ctx)
 }
@@ -85,7 +127,14 @@ public fun claim<TOKEN>(
     assert!(leaf_index < drop.wallet_count as u64);
     assert!(proof.length() == proof_length(drop.wallet_count as u64));
 
-    let res = verify_sender_proof(drop.root, &proof, &ctx.sender(), allocation, leaf_index);
+    let res = verify_sender_proof(
+        tree_version(drop),
+        drop.root,
+        &proof,
+        &ctx.sender(),
+        allocation,
+        leaf_index,
+    );
 
     assert!(res);
 
@@ -95,25 +144,81 @@ public fun claim<TOKEN>(
     coin::from_balance(bal, ctx)
 }
 
+/// Claim for several wallets with one multi-proof, e.g. a custodian's
+/// deposit addresses. Each allocation is sent to its own wallet, so anyone
+/// can submit it. Leaf indices must be ascending.
+public fun claim_many<TOKEN>(
+    wallets: vector<address>,
+    allocations: vector<u64>,
+    leaf_indices: vector<u64>,
+    proof: vector<vector<u8>>,
+    drop: &mut Drop<TOKEN>,
+    ctx: &mut TxContext,
+) {
+    let count = wallets.length();
+    assert!(count > 0);
+    assert!(allocations.length() == count);
+    assert!(leaf_indices.length() == count);
+
+    let version = tree_version(drop);
+    let mut leaves = vector::empty();
+    let mut i = 0;
+    while (i < count) {
+        let wallet = vector::borrow(&wallets, i);
+        leaves.push_back(hash_leaf(version, wallet, *vector::borrow(&allocations, i)));
+        i = i + 1;
+    };
+
+    let res = verify_multi_proof(
+        version,
+        &proof,
+        drop.root,
+        leaves,
+        leaf_indices,
+        drop.wallet_count as u64,
+    );
+
+    assert!(res);
+
+    let mut i = 0;
+    while (i < count) {
+        let wallet = *vector::borrow(&wallets, i);
+        drop.registry.add(wallet, true);
+        let bal = drop.vault.split(*vector::borrow(&allocations, i));
+        transfer::public_transfer(coin::from_balance(bal, ctx), wallet);
+        i = i + 1;
+    };
+}
+
 public fun has_claimed<TOKEN>(addr: address, drop: &Drop<TOKEN>): bool {
     drop.registry.contains(addr)
 }
 
+public fun tree_version<TOKEN>(drop: &Drop<TOKEN>): u8 {
+    if (df::exists_(&drop.id, TREE_VERSION_KEY)) {
+        *df::borrow<vector<u8>, u8>(&drop.id, TREE_VERSION_KEY)
+    } else {
+        TREE_V1
+    }
+}
+
 /// internal
 
 public(package) fun verify_sender_proof(
+    version: u8,
     root: vector<u8>,
     proof: &vector<vector<u8>>,
     sender: &address,
     allocation: u64,
     leaf_index: u64,
 ): bool {
-    let leaf_bytes = hash_address_w_allocation(sender, allocation);
+    let leaf_bytes = hash_leaf(version, sender, allocation);
 
-    verify_proof(proof, root, leaf_bytes, leaf_index)
+    verify_proof(version, proof, root, leaf_bytes, leaf_index)
 }
 
 fun verify_proof(
+    version: u8,
     proof: &vector<vector<u8>>,
     root: vector<u8>,
     leaf: vector<u8>,
@@ -126,11 +231,71 @@ fun verify_proof(
     let node_set = sui::vec_set::from_keys(*proof);
     assert!(node_set.size() == proof.length());
 
-    let computed_hash = compute_proof(leaf, proof, leaf_index);
+    let computed_hash = compute_proof(version, leaf, proof, leaf_index);
     computed_hash == root
 }
 
+/// Mirrors `merkle::verify_multi_proof` in the CLI. Each level pairs the
+/// known nodes, taking missing siblings from `proof` in order, and the last
+/// node of an odd level is paired with itself.
+public(package) fun verify_multi_proof(
+    version: u8,
+    proof: &vector<vector<u8>>,
+    root: vector<u8>,
+    mut leaves: vector<vector<u8>>,
+    mut indices: vector<u64>,
+    leaf_count: u64,
+): bool {
+    assert!(root.length() == 32);
+    assert!(proof.all!(|h| h.length() == 32));
+
+    let count = indices.length();
+    let mut i = 1;
+    while (i < count) {
+        assert!(*vector::borrow(&indices, i - 1) < *vector::borrow(&indices, i));
+        i = i + 1;
+    };
+    assert!(*vector::borrow(&indices, count - 1) < leaf_count);
+
+    let mut next = 0;
+    let mut len = leaf_count;
+    while (len > 1) {
+        let mut parents = vector::empty();
+        let mut parent_indices = vector::empty();
+        let count = indices.length();
+        let mut i = 0;
+        while (i < count) {
+            let index = *vector::borrow(&indices, i);
+            let hash = *vector::borrow(&leaves, i);
+            let parent = if (index % 2 == 1) {
+                if (next == proof.length()) return false;
+                next = next + 1;
+                hash_node(version, *vector::borrow(proof, next - 1), hash)
+            } else if (i + 1 < count && *vector::borrow(&indices, i + 1) == index + 1) {
+                // Both children are known
+                i = i + 1;
+                hash_node(version, hash, *vector::borrow(&leaves, i))
+            } else if (index + 1 < len) {
+                if (next == proof.length()) return false;
+                next = next + 1;
+                hash_node(version, hash, *vector::borrow(proof, next - 1))
+            } else {
+                hash_node(version, hash, hash)
+            };
+            parents.push_back(parent);
+            parent_indices.push_back(index / 2);
+            i = i + 1;
+        };
+        leaves = parents;
+        indices = parent_indices;
+        len = (len + 1) / 2;
+    };
+
+    next == proof.length() && *vector::borrow(&leaves, 0) == root
+}
+
 fun compute_proof(
+    version: u8,
     leaf: vector<u8>,
     proof: &vector<vector<u8>>,
     mut current_index: u64,
@@ -144,10 +309,10 @@ fun compute_proof(
         // Determine ordering based on index
         if (current_index % 2 == 0) {
             // Even index: current_hash is left, sibling is right
-            current_hash = hash_slices(current_hash, sibling);
+            current_hash = hash_node(version, current_hash, sibling);
         } else {
             // Odd index: sibling is left, current_hash is right
-            current_hash = hash_slices(sibling, current_hash);
+            current_hash = hash_node(version, sibling, current_hash);
         };
         // Move to parent index
         current_index = current_index / 2;
@@ -162,7 +327,29 @@ fun hash_slices(mut a: vector<u8>, b: vector<u8>): vector<u8> {
     hash::blake2b256(&a)
 }
 
-fun hash_address_w_allocation(addr: &address, allocation: u64): vector<u8> {
+fun hash_node(version: u8, a: vector<u8>, b: vector<u8>): vector<u8> {
+    if (version == TREE_V2) {
+        let mut bts = vector[NODE_TAG];
+        vector::append(&mut bts, a);
+        vector::append(&mut bts, b);
+        hash::blake2b256(&bts)
+    } else {
+        hash_slices(a, b)
+    }
+}
+
+public(package) fun hash_leaf(version: u8, addr: &address, allocation: u64): vector<u8> {
+    if (version == TREE_V2) {
+        let mut bts = vector[LEAF_TAG];
+        vector::append(&mut bts, sui::bcs::to_bytes(addr));
+        vector::append(&mut bts, sui::bcs::to_bytes(&allocation));
+        hash::blake2b256(&bts)
+    } else {
+        hash_address_w_allocation(addr, allocation)
+    }
+}
+
+public(package) fun hash_address_w_allocation(addr: &address, allocation: u64): vector<u8> {
     let mut bts = vector::empty();
     vector::append(&mut bts, sui::bcs::to_bytes(addr));
     vector::append(&mut bts, sui::bcs::to_bytes(&allocation));
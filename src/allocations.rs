@@ -0,0 +1,243 @@
+//! Binary allocations blob.
+//!
+//! ```text
+//! magic     4 bytes   "LRGA"
+//! version   u16 LE
+//! reserved  u16 LE    0
+//! count     u64 LE
+//! checksum  32 bytes  Blake2b of the rows
+//! rows      count * (32 byte address, u64 LE allocation), sorted by address
+//! ```
+//!
+//! Rows are fixed width, so a wallet is found by binary search without
+//! parsing the whole blob. Older campaigns store allocations as CSV, see
+//! `wallets::parse_csv`.
+
+use anyhow::anyhow;
+use blake2::{Blake2b, Digest};
+use sui_sdk_types::Address;
+
+pub const MAGIC: &[u8; 4] = b"LRGA";
+pub const VERSION: u16 = 1;
+
+pub const HEADER_LEN: usize = 48;
+pub const ROW_LEN: usize = 40;
+
+/// Whether `data` is a binary allocations blob rather than CSV.
+pub fn is_binary(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encode a list sorted by address, as `wallets::clean_addresses` returns it.
+pub fn encode(allocations: &[(Address, u64)]) -> anyhow::Result<Vec<u8>> {
+    if let Some(i) = allocations.windows(2).position(|w| w[0].0 >= w[1].0) {
+        return Err(anyhow!(
+            "allocations must be sorted by address without duplicates, row {} isn't",
+            i + 1
+        ));
+    }
+
+    let mut data = vec![0; HEADER_LEN];
+    data.reserve(allocations.len() * ROW_LEN);
+    for (address, allocation) in allocations {
+        data.extend_from_slice(address.as_bytes());
+        data.extend_from_slice(&allocation.to_le_bytes());
+    }
+    write_header(&mut data);
+    Ok(data)
+}
+
+/// Encode rows in any order, e.g. `wallets::csv_rows`, with the checks of
+/// `wallets::clean_addresses`. Rows are sorted in place in the blob, so the
+/// list is never held twice. Returns the allocations' total and the blob.
+pub fn encode_rows<I>(rows: I) -> anyhow::Result<(u64, Vec<u8>)>
+where
+    I: IntoIterator<Item = anyhow::Result<(Address, u64)>>,
+{
+    let mut data = vec![0; HEADER_LEN];
+    let mut total: u64 = 0;
+    for row in rows {
+        let (address, allocation) = row?;
+        if allocation == 0 {
+            return Err(anyhow!("empty claim"));
+        }
+        total = total
+            .checked_add(allocation)
+            .ok_or(anyhow!("allocations total overflows a u64"))?;
+        data.extend_from_slice(address.as_bytes());
+        data.extend_from_slice(&allocation.to_le_bytes());
+    }
+
+    let (rows, _) = data[HEADER_LEN..].as_chunks_mut::<ROW_LEN>();
+    rows.sort_unstable_by(|a, b| a[..32].cmp(&b[..32]));
+    if rows.windows(2).any(|w| w[0][..32] == w[1][..32]) {
+        return Err(anyhow!("duplicates"));
+    }
+    write_header(&mut data);
+    Ok((total, data))
+}
+
+/// Fill in the header of a blob whose rows follow `HEADER_LEN` zero bytes.
+fn write_header(data: &mut [u8]) {
+    let (header, rows) = data.split_at_mut(HEADER_LEN);
+    let checksum: [u8; 32] = Blake2b::digest(&*rows).into();
+    header[..4].copy_from_slice(MAGIC);
+    header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+    header[6..8].copy_from_slice(&0u16.to_le_bytes());
+    header[8..16].copy_from_slice(&((rows.len() / ROW_LEN) as u64).to_le_bytes());
+    header[16..].copy_from_slice(&checksum);
+}
+
+/// A checked view over a binary allocations blob.
+#[derive(Clone, Copy, Debug)]
+pub struct AllocationTable<'a> {
+    rows: &'a [u8],
+}
+
+impl<'a> AllocationTable<'a> {
+    /// Checks the header, the checksum and the sort order.
+    pub fn parse(data: &'a [u8]) -> anyhow::Result<Self> {
+        if data.len() < HEADER_LEN || !is_binary(data) {
+            return Err(anyhow!("Not a binary allocations blob"));
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != VERSION {
+            return Err(anyhow!("Unsupported allocations blob version: {}", version));
+        }
+        let count = u64::from_le_bytes(data[8..16].try_into()?);
+        let rows = &data[HEADER_LEN..];
+        if rows.len() as u64 != count.saturating_mul(ROW_LEN as u64) {
+            return Err(anyhow!(
+                "Allocations blob has {} bytes of rows, expected {} rows",
+                rows.len(),
+                count
+            ));
+        }
+        let checksum: [u8; 32] = Blake2b::digest(rows).into();
+        if checksum != data[16..HEADER_LEN] {
+            return Err(anyhow!("Allocations blob checksum mismatch"));
+        }
+
+        let table = Self { rows };
+        let unsorted = (1..table.len()).find(|i| table.address(i - 1) >= table.address(*i));
+        if let Some(i) = unsorted {
+            return Err(anyhow!("Allocations blob isn't sorted at row {}", i));
+        }
+        Ok(table)
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len() / ROW_LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn row(&self, index: usize) -> &'a [u8] {
+        &self.rows[index * ROW_LEN..(index + 1) * ROW_LEN]
+    }
+
+    fn address(&self, index: usize) -> &'a [u8] {
+        &self.row(index)[..32]
+    }
+
+    pub fn get(&self, index: usize) -> Option<(Address, u64)> {
+        if index >= self.len() {
+            return None;
+        }
+        let row = self.row(index);
+        let address = Address::new(row[..32].try_into().ok()?);
+        let allocation = u64::from_le_bytes(row[32..].try_into().ok()?);
+        Some((address, allocation))
+    }
+
+    /// Row index and allocation of `wallet`.
+    pub fn find(&self, wallet: &Address) -> Option<(usize, u64)> {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.address(mid).cmp(wallet.as_bytes()) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some((mid, self.get(mid)?.1)),
+            }
+        }
+        None
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Address, u64)> + '_ {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+
+    pub fn to_vec(&self) -> Vec<(Address, u64)> {
+        self.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallets;
+
+    #[test]
+    fn test_allocation_table() {
+        let (_, allocations) = wallets::clean_addresses(
+            (1..=9u8)
+                .rev()
+                .map(|i| (Address::new([i; 32]), i as u64 * 1_000))
+                .collect(),
+        )
+        .unwrap();
+        let data = encode(&allocations).unwrap();
+        assert_eq!(data.len(), HEADER_LEN + 9 * ROW_LEN);
+        assert!(is_binary(&data));
+        assert!(!is_binary(
+            &wallets::write_wallets_to_bytes(&allocations).unwrap()
+        ));
+
+        let table = AllocationTable::parse(&data).unwrap();
+        assert_eq!(table.len(), 9);
+        assert_eq!(table.to_vec(), allocations);
+        assert_eq!(table.get(2), Some((Address::new([3; 32]), 3_000)));
+        assert_eq!(table.get(9), None);
+        assert_eq!(table.find(&Address::new([7; 32])), Some((6, 7_000)));
+        assert_eq!(table.find(&Address::new([1; 32])), Some((0, 1_000)));
+        assert_eq!(table.find(&Address::new([0; 32])), None);
+        assert_eq!(table.find(&Address::new([10; 32])), None);
+
+        let mut corrupt = data.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(AllocationTable::parse(&corrupt).is_err());
+        assert!(AllocationTable::parse(&data[..data.len() - 1]).is_err());
+
+        let mut unsorted = allocations.clone();
+        unsorted.swap(0, 1);
+        assert!(encode(&unsorted).is_err());
+    }
+
+    #[test]
+    fn test_encode_rows() {
+        let rows: Vec<_> = (1..=9u8)
+            .rev()
+            .map(|i| (Address::new([i; 32]), i as u64 * 1_000))
+            .collect();
+        let (total, allocations) = wallets::clean_addresses(rows.clone()).unwrap();
+        let (encoded_total, data) = encode_rows(rows.iter().copied().map(Ok)).unwrap();
+        assert_eq!(encoded_total, total);
+        assert_eq!(data, encode(&allocations).unwrap());
+
+        let mut duplicate = rows.clone();
+        duplicate[3].0 = duplicate[5].0;
+        assert!(encode_rows(duplicate.into_iter().map(Ok)).is_err());
+        let mut empty = rows.clone();
+        empty[2].1 = 0;
+        assert!(encode_rows(empty.into_iter().map(Ok)).is_err());
+        let failed = rows
+            .iter()
+            .copied()
+            .map(Ok)
+            .chain([Err(anyhow!("bad row"))]);
+        assert!(encode_rows(failed).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use crate::merkle::MerkleTree;
+use crate::txns::Drop;
+use crate::wallets;
+use sui_sdk_types::Address;
+
+/// One check of a campaign audit.
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+}
+
+/// Result of checking a campaign's blobs against its `Drop` object.
+#[derive(Clone, Debug, Default)]
+pub struct AuditReport {
+    pub checks: Vec<Check>,
+}
+
+impl AuditReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    fn check(&mut self, name: &'static str, passed: bool, detail: String) {
+        self.checks.push(Check {
+            name,
+            passed,
+            detail,
+        });
+    }
+}
+
+/// Rebuild the tree from the allocations list, and check both blobs against
+/// each other and against the onchain campaign. `tree` is `None` for
+/// campaigns that only store the allocations.
+pub fn audit_drop(
+    drop: &Drop,
+    tree: Option<&MerkleTree>,
+    allocations: &[(Address, u64)],
+) -> AuditReport {
+    let mut report = AuditReport::default();
+
+    let unsorted = allocations.windows(2).position(|w| w[0].0 > w[1].0);
+    report.check(
+        "sort order",
+        unsorted.is_none(),
+        match unsorted {
+            Some(i) => format!("row {} is out of address order", i + 1),
+            None => "allocations are sorted by address".to_string(),
+        },
+    );
+
+    let mut addresses: Vec<_> = allocations.iter().map(|(addr, _)| addr).collect();
+    addresses.sort();
+    let duplicate = addresses.windows(2).find(|w| w[0] == w[1]).map(|w| w[0]);
+    report.check(
+        "duplicates",
+        duplicate.is_none(),
+        match duplicate {
+            Some(addr) => format!("{} appears more than once", addr),
+            None => "no duplicate addresses".to_string(),
+        },
+    );
+
+    let empty = allocations.iter().filter(|(_, allo)| *allo == 0).count();
+    report.check(
+        "empty allocations",
+        empty == 0,
+        format!("{} zero allocations", empty),
+    );
+
+    report.check(
+        "wallet count",
+        allocations.len() == drop.wallet_count as usize,
+        format!(
+            "{} rows, campaign wallet_count is {}",
+            allocations.len(),
+            drop.wallet_count
+        ),
+    );
+
+    let sum: u128 = allocations.iter().map(|(_, allo)| *allo as u128).sum();
+    report.check(
+        "airdrop total",
+        sum == drop.airdrop_total as u128,
+        format!(
+            "allocations sum to {}, campaign airdrop_total is {}",
+            sum, drop.airdrop_total
+        ),
+    );
+
+    if let Some(tree) = tree {
+        report.check(
+            "stored tree version",
+            tree.version == drop.tree_version,
+            format!(
+                "stored tree is {}, campaign expects {}",
+                tree.version, drop.tree_version
+            ),
+        );
+        report.check(
+            "stored tree size",
+            tree.leaf_count == drop.wallet_count,
+            format!(
+                "stored tree has {} leaves, campaign wallet_count is {}",
+                tree.leaf_count, drop.wallet_count
+            ),
+        );
+    }
+
+    let rebuilt = match wallets::build_tree(allocations, drop.tree_version) {
+        Ok(rebuilt) => rebuilt,
+        Err(e) => {
+            report.check("rebuilt tree", false, format!("can't rebuild tree: {}", e));
+            return report;
+        }
+    };
+    let root = rebuilt.get_root();
+
+    report.check(
+        "onchain root",
+        root.as_slice() == drop.root.as_slice(),
+        format!(
+            "rebuilt root 0x{}, campaign root 0x{}",
+            hex::encode(root),
+            hex::encode(&drop.root)
+        ),
+    );
+    match tree {
+        Some(tree) => report.check(
+            "stored tree",
+            rebuilt == *tree,
+            if rebuilt == *tree {
+                "stored tree is identical to the rebuilt one".to_string()
+            } else {
+                format!(
+                    "stored tree differs, its root is 0x{}",
+                    hex::encode(tree.get_root())
+                )
+            },
+        ),
+        None => report.check(
+            "stored tree",
+            true,
+            "no tree blob, claims rebuild the tree from the allocations".to_string(),
+        ),
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle::TreeVersion;
+    use crate::txns::Table;
+
+    fn campaign() -> (Drop, MerkleTree, Vec<(Address, u64)>) {
+        let (total, allocations) = wallets::clean_addresses(
+            (1..=7u8)
+                .map(|i| (Address::new([i; 32]), i as u64 * 100))
+                .collect(),
+        )
+        .unwrap();
+        let tree = wallets::build_tree(&allocations, TreeVersion::V1).unwrap();
+        let drop = Drop {
+            id: [0; 32],
+            root: tree.get_root().to_vec(),
+            wallet_count: allocations.len() as u32,
+            airdrop_total: total,
+            vault: total,
+            allocations: Address::new([1; 32]),
+            merkle_tree: Address::new([2; 32]),
+            registry: Table {
+                id: [3; 32],
+                size: 0,
+            },
+            tree_version: TreeVersion::V1,
+        };
+        (drop, tree, allocations)
+    }
+
+    fn failed(report: &AuditReport) -> Vec<&'static str> {
+        report
+            .checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.name)
+            .collect()
+    }
+
+    #[test]
+    fn test_audit_passes() {
+        let (drop, tree, allocations) = campaign();
+        let report = audit_drop(&drop, Some(&tree), &allocations);
+        assert!(report.passed(), "{:?}", failed(&report));
+    }
+
+    #[test]
+    fn test_audit_without_tree_blob() {
+        let (mut drop, _, mut allocations) = campaign();
+        drop.merkle_tree = Address::ZERO;
+        assert!(drop.tree_blob().is_none());
+        let report = audit_drop(&drop, None, &allocations);
+        assert!(report.passed(), "{:?}", failed(&report));
+
+        allocations[2].1 += 1;
+        let report = audit_drop(&drop, None, &allocations);
+        assert_eq!(failed(&report), vec!["airdrop total", "onchain root"]);
+    }
+
+    #[test]
+    fn test_audit_failures() {
+        let (drop, tree, mut allocations) = campaign();
+
+        allocations[2].1 += 1;
+        let report = audit_drop(&drop, Some(&tree), &allocations);
+        assert_eq!(
+            failed(&report),
+            vec!["airdrop total", "onchain root", "stored tree"]
+        );
+
+        let (drop, tree, mut allocations) = campaign();
+        allocations.swap(0, 1);
+        allocations[3] = allocations[4];
+        let report = audit_drop(&drop, Some(&tree), &allocations);
+        let failures = failed(&report);
+        assert!(failures.contains(&"sort order"));
+        assert!(failures.contains(&"duplicates"));
+
+        let (mut drop, tree, allocations) = campaign();
+        drop.wallet_count += 1;
+        drop.root = vec![0; 32];
+        let report = audit_drop(&drop, Some(&tree), &allocations);
+        assert_eq!(
+            failed(&report),
+            vec!["wallet count", "stored tree size", "onchain root"]
+        );
+    }
+
+    #[test]
+    fn test_audit_tree_version() {
+        let (mut drop, tree, allocations) = campaign();
+        drop.tree_version = TreeVersion::V2;
+        let report = audit_drop(&drop, Some(&tree), &allocations);
+        assert_eq!(
+            failed(&report),
+            vec!["stored tree version", "onchain root", "stored tree"]
+        );
+
+        let tree = wallets::build_tree(&allocations, TreeVersion::V2).unwrap();
+        drop.root = tree.get_root().to_vec();
+        let report = audit_drop(&drop, Some(&tree), &allocations);
+        assert!(report.passed(), "{:?}", failed(&report));
+    }
+}
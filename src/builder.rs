@@ -0,0 +1,327 @@
+//! Multi-threaded merkle tree builder for campaigns too large for
+//! `MerkleTree::new`.
+//!
+//! Leaves are hashed in batches as the allocations are read, then each level
+//! is hashed in parallel from the one below. All levels share one buffer,
+//! leaves first, kept in memory or in a memory-mapped temporary file. The
+//! result is the same tree `MerkleTree::new` builds, with the last node of an
+//! odd level paired with itself.
+
+use crate::merkle::{Hash, MerkleTree, TreeVersion};
+use crate::wallets;
+use anyhow::{anyhow, Context};
+use memmap2::MmapMut;
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use sui_sdk_types::Address;
+
+/// Rows read per batch, and nodes hashed between progress reports.
+const CHUNK: usize = 1 << 16;
+
+/// Nodes hashed so far, out of every node in the tree.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuildProgress {
+    pub done: u64,
+    pub total: u64,
+}
+
+type ProgressFn = Box<dyn Fn(BuildProgress) + Send + Sync>;
+
+#[derive(Default)]
+pub struct TreeBuilder {
+    threads: Option<usize>,
+    spill_dir: Option<PathBuf>,
+    progress: Option<ProgressFn>,
+    version: TreeVersion,
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of worker threads. Defaults to rayon's global pool, one thread
+    /// per core.
+    pub fn threads(self, threads: usize) -> Self {
+        Self {
+            threads: Some(threads),
+            ..self
+        }
+    }
+
+    /// How leaves and nodes are hashed. Defaults to v1.
+    pub fn version(self, version: TreeVersion) -> Self {
+        Self { version, ..self }
+    }
+
+    /// Keep the levels in a memory-mapped temporary file in `dir`, so the
+    /// OS can page them out. Needed past what fits in memory, about 64 bytes
+    /// a leaf.
+    pub fn spill_to(self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            spill_dir: Some(dir.into()),
+            ..self
+        }
+    }
+
+    /// Called from the worker threads as nodes are hashed.
+    pub fn on_progress(self, progress: impl Fn(BuildProgress) + Send + Sync + 'static) -> Self {
+        Self {
+            progress: Some(Box::new(progress)),
+            ..self
+        }
+    }
+
+    /// Build the tree of `leaf_count` allocations in leaf order, i.e. sorted
+    /// by address as `wallets::clean_addresses` returns them. Rows are read
+    /// in batches as they're hashed, so they can come straight from a reader
+    /// such as `allocations::AllocationTable::iter`.
+    pub fn build<I>(&self, leaf_count: usize, allocations: I) -> anyhow::Result<TreeLevels>
+    where
+        I: IntoIterator<Item = (Address, u64)>,
+    {
+        let mut rows = allocations.into_iter();
+        if leaf_count < 2 {
+            return Err(anyhow!("insufficient leaves"));
+        }
+        u32::try_from(leaf_count).context("too many leaves")?;
+
+        let lens = level_lens(leaf_count);
+        let total: usize = lens.iter().sum();
+        let mut storage = match &self.spill_dir {
+            Some(dir) => Storage::mapped(dir, total)?,
+            None => Storage::Memory(vec![[0; 32]; total]),
+        };
+        let pool = match self.threads {
+            Some(threads) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()?,
+            ),
+            None => None,
+        };
+
+        let done = AtomicU64::new(0);
+        let report = |nodes: usize| {
+            let done = done.fetch_add(nodes as u64, Ordering::Relaxed) + nodes as u64;
+            if let Some(progress) = &self.progress {
+                progress(BuildProgress {
+                    done,
+                    total: total as u64,
+                });
+            }
+        };
+
+        let (leaves, mut upper) = storage.nodes_mut().split_at_mut(lens[0]);
+        let mut batch = Vec::with_capacity(CHUNK);
+        for out in leaves.chunks_mut(CHUNK) {
+            batch.clear();
+            batch.extend(rows.by_ref().take(out.len()));
+            if batch.len() != out.len() {
+                return Err(anyhow!("allocations ended before their reported length"));
+            }
+            install(&pool, || {
+                out.par_iter_mut()
+                    .zip(batch.par_iter())
+                    .for_each(|(leaf, (addr, allo))| {
+                        *leaf = wallets::hash_leaf(self.version, addr, *allo)
+                    });
+            });
+            report(out.len());
+        }
+        if rows.next().is_some() {
+            return Err(anyhow!("more allocations than their reported length"));
+        }
+
+        let mut below: &[Hash] = leaves;
+        for len in &lens[1..] {
+            let (level, rest) = std::mem::take(&mut upper).split_at_mut(*len);
+            install(&pool, || {
+                level
+                    .par_chunks_mut(CHUNK)
+                    .enumerate()
+                    .for_each(|(i, out)| {
+                        let pairs = &below[i * 2 * CHUNK..];
+                        for (j, node) in out.iter_mut().enumerate() {
+                            let left = pairs[2 * j];
+                            let right = pairs.get(2 * j + 1).copied().unwrap_or(left);
+                            *node = self.version.hash_node(&left, &right);
+                        }
+                        report(out.len());
+                    });
+            });
+            below = level;
+            upper = rest;
+        }
+
+        Ok(TreeLevels {
+            storage,
+            lens,
+            version: self.version,
+        })
+    }
+}
+
+fn install(pool: &Option<rayon::ThreadPool>, work: impl FnOnce() + Send) {
+    match pool {
+        Some(pool) => pool.install(work),
+        None => work(),
+    }
+}
+
+/// Nodes per level, leaves first, root last.
+fn level_lens(leaf_count: usize) -> Vec<usize> {
+    let mut lens = vec![leaf_count];
+    while let Some(&len) = lens.last().filter(|len| **len > 1) {
+        lens.push(len.div_ceil(2));
+    }
+    lens
+}
+
+enum Storage {
+    Memory(Vec<Hash>),
+    Mapped(MmapMut),
+}
+
+impl Storage {
+    fn mapped(dir: &std::path::Path, nodes: usize) -> anyhow::Result<Self> {
+        let file = tempfile::tempfile_in(dir)
+            .with_context(|| format!("Failed to create tree file in {}", dir.display()))?;
+        file.set_len(nodes as u64 * 32)?;
+        // SAFETY: the file is unlinked and only reachable through this map
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        Ok(Self::Mapped(map))
+    }
+
+    fn nodes(&self) -> &[Hash] {
+        match self {
+            Self::Memory(nodes) => nodes,
+            Self::Mapped(map) => map.as_chunks().0,
+        }
+    }
+
+    fn nodes_mut(&mut self) -> &mut [Hash] {
+        match self {
+            Self::Memory(nodes) => nodes,
+            Self::Mapped(map) => map.as_chunks_mut().0,
+        }
+    }
+}
+
+/// Every level of a built tree, without the leaf index a `MerkleTree` keeps.
+pub struct TreeLevels {
+    storage: Storage,
+    lens: Vec<usize>,
+    version: TreeVersion,
+}
+
+impl TreeLevels {
+    pub fn leaf_count(&self) -> u32 {
+        // Checked to fit when built
+        self.lens[0] as u32
+    }
+
+    pub fn version(&self) -> TreeVersion {
+        self.version
+    }
+
+    pub fn root(&self) -> Hash {
+        self.storage.nodes()[self.storage.nodes().len() - 1]
+    }
+
+    /// Leaves first, root last.
+    pub fn levels(&self) -> Vec<&[Hash]> {
+        let mut rest = self.storage.nodes();
+        self.lens
+            .iter()
+            .map(|len| {
+                let (level, tail) = rest.split_at(*len);
+                rest = tail;
+                level
+            })
+            .collect()
+    }
+
+    /// Copy the levels into memory as a `MerkleTree`.
+    pub fn into_tree(self) -> anyhow::Result<MerkleTree> {
+        let levels = self.levels().into_iter().map(<[Hash]>::to_vec).collect();
+        MerkleTree::from_levels(levels, self.version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::sync::{Arc, Mutex};
+
+    fn allocations(count: usize) -> Vec<(Address, u64)> {
+        (0..count as u64)
+            .map(|i| {
+                let mut bytes = [0; 32];
+                bytes[..8].copy_from_slice(&i.to_be_bytes());
+                (Address::new(bytes), i + 1)
+            })
+            .collect()
+    }
+
+    fn reference(allocations: &[(Address, u64)], version: TreeVersion) -> MerkleTree {
+        let leaves: Vec<_> = allocations
+            .iter()
+            .map(|(addr, allo)| wallets::hash_leaf(version, addr, *allo))
+            .collect();
+        MerkleTree::with_version(&leaves, version).unwrap()
+    }
+
+    #[test]
+    fn test_build_spilled() {
+        let dir = tempfile::tempdir().unwrap();
+        let allocations = allocations(CHUNK * 2 + 3);
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        let levels = TreeBuilder::new()
+            .threads(3)
+            .spill_to(dir.path())
+            .on_progress(move |progress| seen.lock().unwrap().push(progress))
+            .build(allocations.len(), allocations.iter().copied())
+            .unwrap();
+
+        let tree = reference(&allocations, TreeVersion::V1);
+        assert_eq!(levels.root(), tree.get_root());
+        assert_eq!(levels.leaf_count(), tree.leaf_count);
+        assert_eq!(levels.into_tree().unwrap(), tree);
+
+        let reports = reports.lock().unwrap();
+        let last = reports.iter().max_by_key(|progress| progress.done).unwrap();
+        assert_eq!(last.done, last.total);
+    }
+
+    #[test]
+    fn test_build_wrong_count() {
+        assert!(TreeBuilder::new().build(1, allocations(1)).is_err());
+        assert!(TreeBuilder::new().build(5, allocations(4)).is_err());
+        assert!(TreeBuilder::new().build(5, allocations(6)).is_err());
+        // Rows only need to be an iterator, without a known length
+        let rows = allocations(6).into_iter().filter(|(_, allo)| *allo > 2);
+        assert!(TreeBuilder::new().build(4, rows).is_ok());
+    }
+
+    proptest! {
+        #[test]
+        fn test_build_matches_merkle_tree(
+            count in 2usize..300,
+            threads in 1usize..4,
+            v2 in any::<bool>(),
+        ) {
+            let version = if v2 { TreeVersion::V2 } else { TreeVersion::V1 };
+            let allocations = allocations(count);
+            let levels = TreeBuilder::new()
+                .threads(threads)
+                .version(version)
+                .build(count, allocations.iter().copied())
+                .unwrap();
+            prop_assert_eq!(levels.into_tree().unwrap(), reference(&allocations, version));
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use crate::ffi::NewBlob;
+use crate::store::{slice_range, BlobStore};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use blake2::{Blake2b, Digest};
+use bytes::Bytes;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use sui_sdk_types::Address;
+
+/// Cache size limit when nothing else is set, in MiB. Room for both blobs of
+/// a few million-wallet campaigns.
+pub const DEFAULT_CACHE_SIZE_MB: u64 = 2048;
+
+const CHECKSUM_LEN: usize = 32;
+
+/// Downloaded blobs on disk, one file per Walrus blob ID. Each file starts
+/// with a Blake2b checksum of the blob, so a truncated or corrupted entry is
+/// dropped instead of returned. Once a whole entry has been read back and
+/// checked, a `.verified` marker with its length lets range reads seek into
+/// it without hashing it again. Least recently used entries are evicted once
+/// the cache grows past `max_bytes`.
+pub struct BlobCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl BlobCache {
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes,
+        }
+    }
+
+    /// `~/.cache/large`, or the platform's equivalent.
+    pub fn default_dir() -> anyhow::Result<PathBuf> {
+        let cache = dirs::cache_dir().ok_or(anyhow!("No cache directory found"))?;
+        Ok(cache.join("large"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Walrus blob IDs are URL-safe base64. Anything else isn't cached, so an
+    // ID can never point outside the cache directory.
+    fn path(&self, blob_id: &str) -> Option<PathBuf> {
+        let valid = !blob_id.is_empty()
+            && blob_id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        valid.then(|| self.dir.join(blob_id))
+    }
+
+    pub async fn get(&self, blob_id: &str) -> Option<Vec<u8>> {
+        let path = self.path(blob_id)?;
+        let mut data = tokio::fs::read(&path).await.ok()?;
+        let valid = data.len() >= CHECKSUM_LEN && {
+            let (checksum, blob) = data.split_at(CHECKSUM_LEN);
+            let expected: [u8; CHECKSUM_LEN] = Blake2b::digest(blob).into();
+            checksum == expected
+        };
+        if !valid {
+            let _ = tokio::fs::remove_file(&path).await;
+            let _ = tokio::fs::remove_file(marker_path(&path)).await;
+            return None;
+        }
+        if self.verified_len(&path).await != Some(data.len() as u64) {
+            let _ = tokio::fs::write(marker_path(&path), data.len().to_string()).await;
+        }
+        touch(&path).await;
+        data.drain(..CHECKSUM_LEN);
+        Some(data)
+    }
+
+    /// Bytes `range` of a cached blob. Entries that haven't been verified
+    /// yet are read whole and checked first, later reads only read the range.
+    /// `None` when the blob isn't cached or the range is outside it.
+    pub async fn get_range(&self, blob_id: &str, range: Range<u64>) -> Option<Vec<u8>> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let path = self.path(blob_id)?;
+        let Some(len) = self.verified_len(&path).await else {
+            return slice_range(&self.get(blob_id).await?, range).ok();
+        };
+        let blob_len = len.checked_sub(CHECKSUM_LEN as u64)?;
+        if range.start > range.end || range.end > blob_len {
+            return None;
+        }
+        let mut file = tokio::fs::File::open(&path).await.ok()?;
+        file.seek(std::io::SeekFrom::Start(CHECKSUM_LEN as u64 + range.start))
+            .await
+            .ok()?;
+        let mut data = vec![0; (range.end - range.start) as usize];
+        file.read_exact(&mut data).await.ok()?;
+        touch(&path).await;
+        Some(data)
+    }
+
+    /// Length of a verified entry, if its marker still matches the file.
+    async fn verified_len(&self, path: &Path) -> Option<u64> {
+        let marker = tokio::fs::read_to_string(marker_path(path)).await.ok()?;
+        let len = tokio::fs::metadata(path).await.ok()?.len();
+        (marker.parse::<u64>().ok()? == len).then_some(len)
+    }
+
+    pub async fn insert(&self, blob_id: &str, value: &[u8]) -> anyhow::Result<()> {
+        let path = self
+            .path(blob_id)
+            .ok_or(anyhow!("Invalid blob ID: {}", blob_id))?;
+        if value.len() as u64 + CHECKSUM_LEN as u64 > self.max_bytes {
+            return Ok(());
+        }
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .context("Failed to create cache directory")?;
+
+        let checksum: [u8; CHECKSUM_LEN] = Blake2b::digest(value).into();
+        let mut data = Vec::with_capacity(CHECKSUM_LEN + value.len());
+        data.extend_from_slice(&checksum);
+        data.extend_from_slice(value);
+        // Written to a temporary file first, so readers never see half a blob
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, &data)
+            .await
+            .context("Failed to write cache entry")?;
+        tokio::fs::rename(&tmp, &path)
+            .await
+            .context("Failed to write cache entry")?;
+
+        self.evict(&path).await
+    }
+
+    /// Remove the least recently used entries until the cache fits, never
+    /// the entry at `keep`. Other processes may be using the cache too, so
+    /// their temporary files are left alone and entries they removed first
+    /// are skipped.
+    async fn evict(&self, keep: &Path) -> anyhow::Result<()> {
+        let mut entries = Vec::new();
+        let mut total = 0;
+        let mut dir = tokio::fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            // Markers go with their entries
+            if entry
+                .path()
+                .extension()
+                .is_some_and(|ext| ext == "tmp" || ext == "verified")
+            {
+                continue;
+            }
+            let meta = match entry.metadata().await {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if !meta.is_file() {
+                continue;
+            }
+            total += meta.len();
+            if entry.path() != keep {
+                entries.push((meta.modified()?, meta.len(), entry.path()));
+            }
+        }
+
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+            let _ = tokio::fs::remove_file(marker_path(&path)).await;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+fn marker_path(path: &Path) -> PathBuf {
+    path.with_extension("verified")
+}
+
+/// The modified time doubles as the last access time for eviction.
+async fn touch(path: &Path) {
+    if let Ok(file) = tokio::fs::File::options().write(true).open(path).await {
+        let _ = file.into_std().await.set_modified(SystemTime::now());
+    }
+}
+
+/// Reads through a `BlobCache`. Everything else goes straight to the inner
+/// store.
+pub struct CachedStore {
+    inner: Box<dyn BlobStore>,
+    cache: BlobCache,
+}
+
+impl CachedStore {
+    pub fn new(inner: Box<dyn BlobStore>, cache: BlobCache) -> Self {
+        Self { inner, cache }
+    }
+}
+
+#[async_trait]
+impl BlobStore for CachedStore {
+    async fn put(&self, value: Bytes, epochs: u32, deletable: bool) -> anyhow::Result<NewBlob> {
+        self.inner.put(value, epochs, deletable).await
+    }
+
+    async fn get(&self, blob_id: &str) -> anyhow::Result<Vec<u8>> {
+        if let Some(data) = self.cache.get(blob_id).await {
+            return Ok(data);
+        }
+        let data = self.inner.get(blob_id).await?;
+        // A cache that can't be written to shouldn't fail the read
+        let _ = self.cache.insert(blob_id, &data).await;
+        Ok(data)
+    }
+
+    /// Served from the cache when the whole blob is there. Partial reads
+    /// aren't cached.
+    async fn get_range(&self, blob_id: &str, range: Range<u64>) -> anyhow::Result<Vec<u8>> {
+        if let Some(data) = self.cache.get_range(blob_id, range.clone()).await {
+            return Ok(data);
+        }
+        if !self.inner.supports_ranges() {
+            return slice_range(&self.get(blob_id).await?, range);
+        }
+        self.inner.get_range(blob_id, range).await
+    }
+
+    fn supports_ranges(&self) -> bool {
+        self.inner.supports_ranges()
+    }
+
+    async fn extend(&self, object: &Address, epochs: u32) -> anyhow::Result<()> {
+        self.inner.extend(object, epochs).await
+    }
+
+    async fn delete(&self, object: &Address) -> anyhow::Result<()> {
+        self.inner.delete(object).await
+    }
+
+    async fn put_many(
+        &self,
+        values: Vec<Bytes>,
+        epochs: u32,
+        deletable: bool,
+    ) -> anyhow::Result<Vec<NewBlob>> {
+        self.inner.put_many(values, epochs, deletable).await
+    }
+
+    fn blob_id_for_object(&self, object: &Address) -> Option<String> {
+        self.inner.blob_id_for_object(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use std::sync::Arc;
+
+    /// Shares a `MemoryStore` with the test, so blobs can be removed from
+    /// under the cache.
+    struct Shared(Arc<MemoryStore>);
+
+    #[async_trait]
+    impl BlobStore for Shared {
+        async fn put(&self, value: Bytes, epochs: u32, deletable: bool) -> anyhow::Result<NewBlob> {
+            self.0.put(value, epochs, deletable).await
+        }
+
+        async fn get(&self, blob_id: &str) -> anyhow::Result<Vec<u8>> {
+            self.0.get(blob_id).await
+        }
+
+        async fn extend(&self, object: &Address, epochs: u32) -> anyhow::Result<()> {
+            self.0.extend(object, epochs).await
+        }
+
+        async fn delete(&self, object: &Address) -> anyhow::Result<()> {
+            self.0.delete(object).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cached_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let inner = Arc::new(MemoryStore::new());
+        let store = CachedStore::new(
+            Box::new(Shared(inner.clone())),
+            BlobCache::new(dir.path(), 1_000),
+        );
+
+        let blob = store
+            .put(Bytes::from_static(b"allocations"), 1, true)
+            .await
+            .unwrap();
+        assert_eq!(store.get(&blob.blob_id).await.unwrap(), b"allocations");
+
+        // Served from disk once the store no longer has it
+        inner.delete(&blob.object_address).await.unwrap();
+        assert_eq!(store.get(&blob.blob_id).await.unwrap(), b"allocations");
+
+        // A corrupted entry is dropped rather than returned
+        let path = dir.path().join(&blob.blob_id);
+        let mut data = std::fs::read(&path).unwrap();
+        *data.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, data).unwrap();
+        assert!(store.get(&blob.blob_id).await.is_err());
+        assert!(!path.exists());
+
+        assert!(store.cache.get("../config.toml").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cache_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::new(dir.path(), 1_000);
+        let path = dir.path().join("tree");
+        let blob: Vec<u8> = (0..100).collect();
+        cache.insert("tree", &blob).await.unwrap();
+
+        // The first read checks the whole entry
+        assert_eq!(cache.get_range("tree", 10..13).await.unwrap(), [10, 11, 12]);
+        assert!(marker_path(&path).exists());
+        assert!(cache.get_range("tree", 90..101).await.is_none());
+
+        // Later reads seek, so a verified entry isn't hashed again
+        let mut data = std::fs::read(&path).unwrap();
+        data[CHECKSUM_LEN + 50] ^= 1;
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(cache.get_range("tree", 20..22).await.unwrap(), [20, 21]);
+
+        // but one that changed length is checked again, and dropped
+        data.pop();
+        std::fs::write(&path, &data).unwrap();
+        assert!(cache.get_range("tree", 20..22).await.is_none());
+        assert!(!path.exists());
+        assert!(!marker_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_cache_eviction() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = BlobCache::new(dir.path(), 2 * (CHECKSUM_LEN as u64 + 100));
+        // Another process's insert, still being written
+        let pending = dir.path().join("pending.tmp");
+        std::fs::write(&pending, [5; 100]).unwrap();
+
+        cache.insert("first", &[1; 100]).await.unwrap();
+        cache.insert("second", &[2; 100]).await.unwrap();
+        // Touch the first entry, so the second is the least recently used
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(cache.get("first").await.is_some());
+
+        cache.insert("third", &[3; 100]).await.unwrap();
+        assert!(cache.get("first").await.is_some());
+        assert!(cache.get("second").await.is_none());
+        assert!(cache.get("third").await.is_some());
+        assert!(pending.exists());
+
+        // Too large to ever fit
+        cache.insert("huge", &[4; 1_000]).await.unwrap();
+        assert!(cache.get("huge").await.is_none());
+    }
+}
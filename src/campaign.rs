@@ -0,0 +1,315 @@
+use crate::merkle;
+use crate::network::Network;
+use crate::signer::Signer;
+use crate::CampaignBlobs;
+use anyhow::{anyhow, Context};
+use blake2::{Blake2b, Digest};
+use std::path::{Path, PathBuf};
+use sui_sdk_types::{Address, ObjectId, Transaction, TransactionDigest, TypeTag, UserSignature};
+
+/// Progress of a `create-drop` run, saved after every stage so a failed run
+/// can be resumed without uploading the blobs again.
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct CreateState {
+    pub csv_path: PathBuf,
+    pub network: Network,
+    pub package_id: Address,
+    pub coin_type: TypeTag,
+    pub epochs: u32,
+    pub deletable: bool,
+    /// Whether the blobs are zstd compressed before upload.
+    #[serde(default)]
+    pub compress: bool,
+    /// Whether only the allocations are uploaded, leaving claimers to
+    /// rebuild the tree.
+    #[serde(default)]
+    pub skip_tree: bool,
+    /// How the tree is hashed. States from before v2 trees are v1.
+    #[serde(default)]
+    pub tree_version: merkle::TreeVersion,
+    pub list: Option<ValidatedList>,
+    pub tree: Option<BuiltTree>,
+    pub blobs: Option<CampaignBlobs>,
+    pub tx_digest: Option<TransactionDigest>,
+    pub drop_id: Option<ObjectId>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ValidatedList {
+    pub wallet_count: u32,
+    pub total: u64,
+    /// Blake2b hash of the allocations blob, hex encoded.
+    pub checksum: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct BuiltTree {
+    /// Hex encoded.
+    pub root: String,
+    pub leaf_count: u32,
+}
+
+/// The last stage a run completed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Started,
+    Validated,
+    TreeBuilt,
+    Uploaded,
+    Submitted,
+    Created,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Stage::Started => "started",
+            Stage::Validated => "validated list",
+            Stage::TreeBuilt => "built tree",
+            Stage::Uploaded => "uploaded blobs",
+            Stage::Submitted => "submitted transaction",
+            Stage::Created => "created campaign",
+        })
+    }
+}
+
+impl CreateState {
+    pub fn new(
+        csv_path: PathBuf,
+        network: Network,
+        package_id: Address,
+        coin_type: TypeTag,
+        epochs: u32,
+        deletable: bool,
+        compress: bool,
+    ) -> Self {
+        Self {
+            csv_path,
+            network,
+            package_id,
+            coin_type,
+            epochs,
+            deletable,
+            compress,
+            skip_tree: false,
+            tree_version: merkle::TreeVersion::V1,
+            list: None,
+            tree: None,
+            blobs: None,
+            tx_digest: None,
+            drop_id: None,
+        }
+    }
+
+    /// Where the state for a CSV file is kept unless another path is given.
+    pub fn default_path(csv_path: &Path) -> PathBuf {
+        let mut name = csv_path.as_os_str().to_owned();
+        name.push(".large-state.json");
+        PathBuf::from(name)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read state file: {}", path.display()))?;
+        serde_json::from_str(&data).context("Malformed state file")
+    }
+
+    /// Written to a temporary file first, so a crash never leaves a
+    /// half-written state behind.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write state file: {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to write state file: {}", path.display()))
+    }
+
+    pub fn stage(&self) -> Stage {
+        if self.drop_id.is_some() {
+            Stage::Created
+        } else if self.tx_digest.is_some() {
+            Stage::Submitted
+        } else if self.blobs.is_some() {
+            Stage::Uploaded
+        } else if self.tree.is_some() {
+            Stage::TreeBuilt
+        } else if self.list.is_some() {
+            Stage::Validated
+        } else {
+            Stage::Started
+        }
+    }
+
+    /// Record the validated list, or check it matches the one recorded
+    /// earlier. Blobs already uploaded must not be paired with another list.
+    pub fn record_list(
+        &mut self,
+        wallet_count: u32,
+        total: u64,
+        allocations: &[u8],
+    ) -> anyhow::Result<()> {
+        let checksum: [u8; 32] = Blake2b::digest(allocations).into();
+        let list = ValidatedList {
+            wallet_count,
+            total,
+            checksum: hex::encode(checksum),
+        };
+        match &self.list {
+            Some(recorded) if *recorded != list => Err(anyhow!(
+                "{} has changed since this campaign was started",
+                self.csv_path.display()
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.list = Some(list);
+                Ok(())
+            }
+        }
+    }
+
+    /// Sign the create transaction, then record its digest just before it's
+    /// submitted. A transaction that was never signed can't have landed, so
+    /// a signing failure leaves the run at the uploaded stage.
+    pub async fn sign_create_tx(
+        &mut self,
+        path: &Path,
+        signer: &dyn Signer,
+        tx: &Transaction,
+    ) -> anyhow::Result<UserSignature> {
+        let sig = signer.sign_tx(tx).await?;
+        self.tx_digest = Some(tx.digest());
+        self.save(path)?;
+        Ok(sig)
+    }
+
+    pub fn record_tree(&mut self, root: &merkle::Hash, leaf_count: u32) -> anyhow::Result<()> {
+        let tree = BuiltTree {
+            root: hex::encode(root),
+            leaf_count,
+        };
+        match &self.tree {
+            Some(recorded) if *recorded != tree => Err(anyhow!(
+                "Merkle root {} doesn't match the recorded root {}",
+                tree.root,
+                recorded.root
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.tree = Some(tree);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::NewBlob;
+    use crate::keystore::tests::test_tx;
+    use crate::keystore::PrivateKey;
+    use crate::signer::KeySigner;
+    use std::str::FromStr;
+
+    struct FailingSigner;
+
+    #[async_trait::async_trait]
+    impl Signer for FailingSigner {
+        async fn address(&self) -> anyhow::Result<Address> {
+            Ok(Address::new([3; 32]))
+        }
+
+        async fn sign_tx(&self, _tx: &Transaction) -> anyhow::Result<UserSignature> {
+            Err(anyhow!("signer unavailable"))
+        }
+    }
+
+    #[test]
+    fn test_create_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv = dir.path().join("wallets.csv");
+        let path = CreateState::default_path(&csv);
+        assert_eq!(path, dir.path().join("wallets.csv.large-state.json"));
+
+        let mut state = CreateState::new(
+            csv,
+            Network::Testnet,
+            Address::new([1; 32]),
+            TypeTag::from_str("0x2::sui::SUI").unwrap(),
+            4,
+            true,
+            false,
+        );
+        assert_eq!(state.stage(), Stage::Started);
+
+        state.record_list(2, 300, b"list").unwrap();
+        state.record_tree(&[7; 32], 2).unwrap();
+        assert_eq!(state.stage(), Stage::TreeBuilt);
+
+        let blob = |i: u8| NewBlob {
+            blob_id: format!("blob{}", i),
+            object_address: Address::new([i; 32]),
+        };
+        state.blobs = Some(CampaignBlobs {
+            merkle_tree: Some(blob(1)),
+            allocations: blob(2),
+        });
+        state.save(&path).unwrap();
+
+        let mut loaded = CreateState::load(&path).unwrap();
+        assert_eq!(loaded.stage(), Stage::Uploaded);
+        assert_eq!(loaded.coin_type, state.coin_type);
+        assert_eq!(
+            loaded.blobs.as_ref().unwrap().allocations.object_address,
+            Address::new([2; 32])
+        );
+
+        // Re-recording the same list and tree on resume is fine
+        loaded.record_list(2, 300, b"list").unwrap();
+        loaded.record_tree(&[7; 32], 2).unwrap();
+        assert!(loaded.record_list(2, 300, b"edited").is_err());
+        assert!(loaded.record_tree(&[8; 32], 2).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sign_create_tx() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let mut state = CreateState::new(
+            dir.path().join("wallets.csv"),
+            Network::Testnet,
+            Address::new([1; 32]),
+            TypeTag::from_str("0x2::sui::SUI").unwrap(),
+            4,
+            true,
+            false,
+        );
+        state.blobs = Some(CampaignBlobs {
+            merkle_tree: None,
+            allocations: NewBlob {
+                blob_id: "blob".to_string(),
+                object_address: Address::new([2; 32]),
+            },
+        });
+        state.save(&path).unwrap();
+
+        // Nothing was sent, so resume must not wait on the digest
+        let tx = test_tx(Address::new([3; 32]));
+        assert!(state
+            .sign_create_tx(&path, &FailingSigner, &tx)
+            .await
+            .is_err());
+        assert_eq!(state.stage(), Stage::Uploaded);
+        assert_eq!(CreateState::load(&path).unwrap().stage(), Stage::Uploaded);
+
+        let key = PrivateKey::from_bytes(&[[0].as_slice(), &[4; 32]].concat()).unwrap();
+        let tx = test_tx(key.address());
+        state
+            .sign_create_tx(&path, &KeySigner::new(key), &tx)
+            .await
+            .unwrap();
+        let loaded = CreateState::load(&path).unwrap();
+        assert_eq!(loaded.stage(), Stage::Submitted);
+        assert_eq!(loaded.tx_digest, Some(tx.digest()));
+    }
+}
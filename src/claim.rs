@@ -0,0 +1,541 @@
+use crate::merkle::{self, MerkleTree, TreeVersion};
+use crate::txns::Drop;
+use crate::{wallets, AllocationExt};
+use sui_sdk_types::Address;
+
+/// Why the campaign blobs can't produce a claim the onchain campaign will
+/// accept.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClaimError {
+    /// The tree blob's root isn't the root recorded in the `Drop`.
+    RootMismatch {
+        tree: merkle::Hash,
+        onchain: Vec<u8>,
+    },
+    /// The tree is hashed differently from what the `Drop` expects.
+    VersionMismatch {
+        tree: TreeVersion,
+        onchain: TreeVersion,
+    },
+    /// The tree blob has a different number of leaves than the campaign has
+    /// wallets.
+    LeafCountMismatch {
+        leaf_count: u32,
+        wallet_count: u32,
+    },
+    NoAllocation(Address),
+    /// The wallet's allocation is in the allocations blob, but its leaf isn't
+    /// in the tree.
+    LeafMissing(Address),
+    /// The proof built from the tree doesn't lead to the onchain root.
+    InvalidProof(Address),
+    /// A multi-claim without any wallets.
+    NoWallets,
+}
+
+impl std::fmt::Display for ClaimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClaimError::RootMismatch { tree, onchain } => write!(
+                f,
+                "merkle tree root 0x{} doesn't match the campaign root 0x{}",
+                hex::encode(tree),
+                hex::encode(onchain)
+            ),
+            ClaimError::VersionMismatch { tree, onchain } => write!(
+                f,
+                "merkle tree is a {} tree but the campaign expects {}",
+                tree, onchain
+            ),
+            ClaimError::LeafCountMismatch {
+                leaf_count,
+                wallet_count,
+            } => write!(
+                f,
+                "merkle tree has {} leaves but the campaign has {} wallets",
+                leaf_count, wallet_count
+            ),
+            ClaimError::NoAllocation(wallet) => write!(f, "no allocation found for {}", wallet),
+            ClaimError::LeafMissing(wallet) => {
+                write!(f, "{} is missing from the merkle tree", wallet)
+            }
+            ClaimError::InvalidProof(wallet) => write!(
+                f,
+                "proof for {} doesn't verify against the campaign root",
+                wallet
+            ),
+            ClaimError::NoWallets => write!(f, "no wallets to claim for"),
+        }
+    }
+}
+
+impl std::error::Error for ClaimError {}
+
+/// Check the tree blob belongs to the onchain campaign.
+pub fn check_tree(drop: &Drop, tree: &MerkleTree) -> Result<(), ClaimError> {
+    check_root(drop, tree.version, tree.get_root(), tree.leaf_count)
+}
+
+/// Check a tree's root, version and size against the onchain campaign.
+pub fn check_root(
+    drop: &Drop,
+    version: TreeVersion,
+    root: merkle::Hash,
+    leaf_count: u32,
+) -> Result<(), ClaimError> {
+    if version != drop.tree_version {
+        return Err(ClaimError::VersionMismatch {
+            tree: version,
+            onchain: drop.tree_version,
+        });
+    }
+    if root.as_slice() != drop.root.as_slice() {
+        return Err(ClaimError::RootMismatch {
+            tree: root,
+            onchain: drop.root.clone(),
+        });
+    }
+    if leaf_count != drop.wallet_count {
+        return Err(ClaimError::LeafCountMismatch {
+            leaf_count,
+            wallet_count: drop.wallet_count,
+        });
+    }
+    Ok(())
+}
+
+/// Everything a wallet needs to call `drop::claim`.
+#[derive(Clone, Debug)]
+pub struct ClaimProof {
+    pub wallet: Address,
+    pub allocation: u64,
+    pub leaf_index: u64,
+    pub proof: merkle::Proof,
+    pub root: merkle::Hash,
+    pub version: TreeVersion,
+}
+
+impl ClaimProof {
+    pub fn new(
+        tree: &MerkleTree,
+        allocations: &Vec<(Address, u64)>,
+        wallet: &Address,
+    ) -> Result<Self, ClaimError> {
+        let allocation = allocations
+            .get_allocation(wallet)
+            .ok_or(ClaimError::NoAllocation(*wallet))?;
+        let leaf = wallets::hash_leaf(tree.version, wallet, allocation);
+        if tree.get_leaf_index(&leaf).is_none() {
+            return Err(ClaimError::LeafMissing(*wallet));
+        }
+        let (leaf_index, proof) = tree.get_proof(&leaf);
+
+        Ok(Self {
+            wallet: *wallet,
+            allocation,
+            leaf_index,
+            proof,
+            root: tree.get_root(),
+            version: tree.version,
+        })
+    }
+
+    /// Build a proof only once the blobs are known to match the onchain
+    /// campaign, so a claim that would abort is never submitted.
+    pub fn for_drop(
+        drop: &Drop,
+        tree: &MerkleTree,
+        allocations: &Vec<(Address, u64)>,
+        wallet: &Address,
+    ) -> Result<Self, ClaimError> {
+        check_tree(drop, tree)?;
+        let claim = Self::new(tree, allocations, wallet)?;
+        if !claim.verify() {
+            return Err(ClaimError::InvalidProof(*wallet));
+        }
+        Ok(claim)
+    }
+
+    /// Build a claim from a single leaf's proof, read without the rest of the
+    /// tree, checking it against the onchain campaign.
+    pub fn from_leaf_proof(
+        drop: &Drop,
+        leaf_proof: &merkle::LeafProof,
+        wallet: &Address,
+        allocation: u64,
+    ) -> Result<Self, ClaimError> {
+        check_root(
+            drop,
+            leaf_proof.version,
+            leaf_proof.root,
+            leaf_proof.leaf_count,
+        )?;
+        if leaf_proof.leaf != wallets::hash_leaf(leaf_proof.version, wallet, allocation) {
+            return Err(ClaimError::LeafMissing(*wallet));
+        }
+        let claim = Self {
+            wallet: *wallet,
+            allocation,
+            leaf_index: leaf_proof.leaf_index,
+            proof: leaf_proof.proof.clone(),
+            root: leaf_proof.root,
+            version: leaf_proof.version,
+        };
+        if !claim.verify() {
+            return Err(ClaimError::InvalidProof(*wallet));
+        }
+        Ok(claim)
+    }
+
+    pub fn leaf(&self) -> merkle::Hash {
+        wallets::hash_leaf(self.version, &self.wallet, self.allocation)
+    }
+
+    pub fn verify(&self) -> bool {
+        merkle::verify_proof(
+            self.version,
+            &self.root,
+            &self.leaf(),
+            &self.proof,
+            self.leaf_index,
+        )
+    }
+
+    /// BCS of the pure arguments of `drop::claim`, in call order: `proof`,
+    /// `leaf_index` and `allocation`. The drop object is the fourth argument.
+    pub fn claim_args(&self) -> anyhow::Result<[Vec<u8>; 3]> {
+        let proof: Vec<Vec<u8>> = self.proof.iter().map(|x| x.to_vec()).collect();
+        Ok([
+            bcs::to_bytes(&proof)?,
+            bcs::to_bytes(&self.leaf_index)?,
+            bcs::to_bytes(&self.allocation)?,
+        ])
+    }
+}
+
+/// Everything needed to call `drop::claim_many` for several wallets.
+#[derive(Clone, Debug)]
+pub struct MultiClaim {
+    /// Wallets and allocations, in the order of `proof.leaf_indices`.
+    pub wallets: Vec<(Address, u64)>,
+    pub proof: merkle::MultiProof,
+    pub root: merkle::Hash,
+    pub leaf_count: u32,
+    pub version: TreeVersion,
+}
+
+impl MultiClaim {
+    /// Like `ClaimProof::for_drop`, for several wallets at once. Repeated
+    /// wallets are claimed once.
+    pub fn for_drop(
+        drop: &Drop,
+        tree: &MerkleTree,
+        allocations: &Vec<(Address, u64)>,
+        wallets: &[Address],
+    ) -> Result<Self, ClaimError> {
+        check_tree(drop, tree)?;
+        let mut rows = Vec::with_capacity(wallets.len());
+        for wallet in wallets {
+            let allocation = allocations
+                .get_allocation(wallet)
+                .ok_or(ClaimError::NoAllocation(*wallet))?;
+            let leaf_index = tree
+                .get_leaf_index(&wallets::hash_leaf(tree.version, wallet, allocation))
+                .ok_or(ClaimError::LeafMissing(*wallet))?;
+            rows.push((leaf_index, (*wallet, allocation)));
+        }
+        rows.sort_unstable_by_key(|(leaf_index, _)| *leaf_index);
+        rows.dedup_by_key(|(leaf_index, _)| *leaf_index);
+
+        let leaf_indices: Vec<u64> = rows.iter().map(|(leaf_index, _)| *leaf_index).collect();
+        let proof = tree
+            .multi_proof(&leaf_indices)
+            .ok_or(ClaimError::NoWallets)?;
+        let claim = Self {
+            wallets: rows.into_iter().map(|(_, row)| row).collect(),
+            proof,
+            root: tree.get_root(),
+            leaf_count: tree.leaf_count,
+            version: tree.version,
+        };
+        if !claim.verify() {
+            return Err(ClaimError::InvalidProof(claim.wallets[0].0));
+        }
+        Ok(claim)
+    }
+
+    /// Like `for_drop`, from the single leaf proofs of `rows`, in the same
+    /// order. Used when the tree is read a proof at a time.
+    pub fn from_leaf_proofs(
+        drop: &Drop,
+        rows: &[(Address, u64)],
+        proofs: &[merkle::LeafProof],
+    ) -> Result<Self, ClaimError> {
+        let first = proofs.first().ok_or(ClaimError::NoWallets)?;
+        check_root(drop, first.version, first.root, first.leaf_count)?;
+        for ((wallet, allocation), leaf_proof) in rows.iter().zip(proofs) {
+            if leaf_proof.leaf != wallets::hash_leaf(leaf_proof.version, wallet, *allocation) {
+                return Err(ClaimError::LeafMissing(*wallet));
+            }
+        }
+        let proof = merkle::MultiProof::from_leaf_proofs(proofs)
+            .ok_or(ClaimError::InvalidProof(rows[0].0))?;
+        // Rows in the order of the proof's leaves, repeated wallets once
+        let mut by_leaf: Vec<_> = proofs.iter().map(|p| p.leaf_index).zip(rows).collect();
+        by_leaf.sort_unstable_by_key(|(leaf_index, _)| *leaf_index);
+        by_leaf.dedup_by_key(|(leaf_index, _)| *leaf_index);
+        let claim = Self {
+            wallets: by_leaf.into_iter().map(|(_, row)| *row).collect(),
+            proof,
+            root: first.root,
+            leaf_count: first.leaf_count,
+            version: first.version,
+        };
+        if !claim.verify() {
+            return Err(ClaimError::InvalidProof(claim.wallets[0].0));
+        }
+        Ok(claim)
+    }
+
+    pub fn verify(&self) -> bool {
+        let leaves: Vec<_> = self
+            .wallets
+            .iter()
+            .map(|(wallet, allocation)| wallets::hash_leaf(self.version, wallet, *allocation))
+            .collect();
+        self.proof
+            .verify(self.version, &self.root, self.leaf_count, &leaves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_claim_proof() {
+        let (_, allocations) = wallets::clean_addresses(
+            (1..=5u8)
+                .map(|i| (Address::new([i; 32]), i as u64 * 10))
+                .collect(),
+        )
+        .unwrap();
+        let leaves: Vec<_> = allocations
+            .iter()
+            .map(|(addr, allo)| wallets::hash_allo(addr, *allo))
+            .collect();
+        let tree = MerkleTree::new(&leaves).unwrap();
+
+        let wallet = Address::new([4; 32]);
+        let claim = ClaimProof::new(&tree, &allocations, &wallet).unwrap();
+        assert_eq!(claim.allocation, 40);
+        assert_eq!(claim.leaf_index, 3);
+        assert_eq!(claim.root, tree.get_root());
+        assert!(claim.verify());
+
+        let [proof, leaf_index, allocation] = claim.claim_args().unwrap();
+        let decoded: Vec<Vec<u8>> = bcs::from_bytes(&proof).unwrap();
+        assert_eq!(decoded.len(), claim.proof.len());
+        assert_eq!(leaf_index, 3u64.to_le_bytes());
+        assert_eq!(allocation, 40u64.to_le_bytes());
+
+        assert_eq!(
+            ClaimProof::new(&tree, &allocations, &Address::new([9; 32])).unwrap_err(),
+            ClaimError::NoAllocation(Address::new([9; 32]))
+        );
+    }
+
+    #[test]
+    fn test_claim_for_drop() {
+        let (total, allocations) = wallets::clean_addresses(
+            (1..=5u8)
+                .map(|i| (Address::new([i; 32]), i as u64 * 10))
+                .collect(),
+        )
+        .unwrap();
+        let leaves: Vec<_> = allocations
+            .iter()
+            .map(|(addr, allo)| wallets::hash_allo(addr, *allo))
+            .collect();
+        let tree = MerkleTree::new(&leaves).unwrap();
+        let mut drop = Drop {
+            id: [0; 32],
+            root: tree.get_root().to_vec(),
+            wallet_count: 5,
+            airdrop_total: total,
+            vault: total,
+            allocations: Address::new([1; 32]),
+            merkle_tree: Address::new([2; 32]),
+            registry: crate::txns::Table {
+                id: [3; 32],
+                size: 0,
+            },
+            tree_version: TreeVersion::V1,
+        };
+        let wallet = Address::new([2; 32]);
+        assert!(ClaimProof::for_drop(&drop, &tree, &allocations, &wallet).is_ok());
+
+        // An allocations blob that doesn't match the tree
+        let mut edited = allocations.clone();
+        edited[1].1 += 1;
+        assert_eq!(
+            ClaimProof::for_drop(&drop, &tree, &edited, &wallet).unwrap_err(),
+            ClaimError::LeafMissing(wallet)
+        );
+
+        drop.tree_version = TreeVersion::V2;
+        assert_eq!(
+            ClaimProof::for_drop(&drop, &tree, &allocations, &wallet).unwrap_err(),
+            ClaimError::VersionMismatch {
+                tree: TreeVersion::V1,
+                onchain: TreeVersion::V2,
+            }
+        );
+        let v2_tree = wallets::build_tree(&allocations, TreeVersion::V2).unwrap();
+        drop.root = v2_tree.get_root().to_vec();
+        let claim = ClaimProof::for_drop(&drop, &v2_tree, &allocations, &wallet).unwrap();
+        assert_eq!(claim.version, TreeVersion::V2);
+        assert_eq!(
+            claim.leaf(),
+            wallets::hash_leaf(TreeVersion::V2, &wallet, 20)
+        );
+
+        drop.wallet_count = 6;
+        assert!(matches!(
+            ClaimProof::for_drop(&drop, &v2_tree, &allocations, &wallet),
+            Err(ClaimError::LeafCountMismatch { .. })
+        ));
+
+        drop.root = vec![0; 32];
+        assert!(matches!(
+            ClaimProof::for_drop(&drop, &v2_tree, &allocations, &wallet),
+            Err(ClaimError::RootMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_multi_claim() {
+        let (total, allocations) = wallets::clean_addresses(
+            (1..=5u8)
+                .map(|i| {
+                    let mut bytes = [0; 32];
+                    bytes[31] = i;
+                    (Address::new(bytes), i as u64 * 10)
+                })
+                .collect(),
+        )
+        .unwrap();
+        let tree = wallets::build_tree(&allocations, TreeVersion::V1).unwrap();
+        // The same tree as `test_multi_proof` in tests/drop_tests.move
+        assert_eq!(
+            hex::encode(tree.get_root()),
+            "fb598ee59b7af97e1ce9fc51afbd3b938420901fa3f24027ce5d810d06fe11c8"
+        );
+        let drop = Drop {
+            id: [0; 32],
+            root: tree.get_root().to_vec(),
+            wallet_count: 5,
+            airdrop_total: total,
+            vault: total,
+            allocations: Address::new([1; 32]),
+            merkle_tree: Address::new([2; 32]),
+            registry: crate::txns::Table {
+                id: [3; 32],
+                size: 0,
+            },
+            tree_version: TreeVersion::V1,
+        };
+
+        let wallet = |i: usize| allocations[i].0;
+        let claim = MultiClaim::for_drop(
+            &drop,
+            &tree,
+            &allocations,
+            &[wallet(4), wallet(1), wallet(2), wallet(1)],
+        )
+        .unwrap();
+        assert_eq!(claim.proof.leaf_indices, vec![1, 2, 4]);
+        assert_eq!(
+            claim.wallets,
+            vec![allocations[1], allocations[2], allocations[4]]
+        );
+        assert_eq!(
+            claim
+                .proof
+                .nodes
+                .iter()
+                .map(hex::encode)
+                .collect::<Vec<_>>(),
+            vec![
+                "c5e6ea8d59dc94b456a54e9efbcb63fdbb6b2efd51255a36887577b649fd6dbb",
+                "f4ebba3a25503c3cc1f92b96a21e15fef5373b9a7564fbaca4461f7bb268d7ff",
+            ]
+        );
+        assert!(claim.verify());
+
+        assert_eq!(
+            MultiClaim::for_drop(&drop, &tree, &allocations, &[]).unwrap_err(),
+            ClaimError::NoWallets
+        );
+        assert_eq!(
+            MultiClaim::for_drop(&drop, &tree, &allocations, &[Address::new([9; 32])]).unwrap_err(),
+            ClaimError::NoAllocation(Address::new([9; 32]))
+        );
+
+        // The same tree as `test_tree_v2` in tests/drop_tests.move
+        let tree = wallets::build_tree(&allocations, TreeVersion::V2).unwrap();
+        assert_eq!(
+            hex::encode(tree.get_root()),
+            "294d5f593ac9bd4a81c966da94536849a0258e1314e21a00f07d87adef387708"
+        );
+        let drop = Drop {
+            root: tree.get_root().to_vec(),
+            tree_version: TreeVersion::V2,
+            ..drop
+        };
+        let claim = ClaimProof::for_drop(&drop, &tree, &allocations, &wallet(3)).unwrap();
+        assert_eq!(
+            claim.proof.iter().map(hex::encode).collect::<Vec<_>>(),
+            vec![
+                "2ac17acfe6afbe4f60e22ee1f97e443613fdef577bd26bb33a4d827aa15d9561",
+                "1923cdfb47896600ab5616b3299b28824bb7d52df4492ff5a8a435b72a7c87a6",
+                "9d9e1edd74f7dcbdfe5dc2986a8d3eb7811a637e40e103fa31d31c245b382e70",
+            ]
+        );
+        let claim = MultiClaim::for_drop(
+            &drop,
+            &tree,
+            &allocations,
+            &[wallet(1), wallet(2), wallet(4)],
+        )
+        .unwrap();
+        assert_eq!(
+            claim
+                .proof
+                .nodes
+                .iter()
+                .map(hex::encode)
+                .collect::<Vec<_>>(),
+            vec![
+                "516c5381cc4e8d8328deeb655f90ac7c5134fbf3d7de4e184841532b35e393bf",
+                "bb200954edd4e1dd4604695c2a82c6418b95b1d67537e4df88fb2a618c8f5972",
+            ]
+        );
+        assert!(claim.verify());
+
+        // The same claim from the wallets' single proofs
+        let rows: Vec<_> = [4, 1, 2, 1].map(|i| allocations[i]).to_vec();
+        let proofs: Vec<_> = [4, 1, 2, 1]
+            .map(|i| tree.leaf_proof(i as u64).unwrap())
+            .to_vec();
+        let from_proofs = MultiClaim::from_leaf_proofs(&drop, &rows, &proofs).unwrap();
+        assert_eq!(from_proofs.wallets, claim.wallets);
+        assert_eq!(from_proofs.proof, claim.proof);
+        let mut swapped = rows.clone();
+        swapped.swap(0, 1);
+        assert_eq!(
+            MultiClaim::from_leaf_proofs(&drop, &swapped, &proofs).unwrap_err(),
+            ClaimError::LeafMissing(swapped[0].0)
+        );
+    }
+}
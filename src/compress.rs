@@ -0,0 +1,100 @@
+//! Optional zstd compression for campaign blobs.
+//!
+//! A compressed blob is `MAGIC`, the uncompressed length as a u64 LE, then a
+//! zstd frame. Blobs without the magic are read as they are, so campaigns
+//! uploaded uncompressed keep loading.
+
+use anyhow::{anyhow, Context};
+use std::borrow::Cow;
+use std::io::Read;
+
+pub const MAGIC: &[u8; 4] = b"LRGZ";
+
+const HEADER_LEN: usize = 12;
+
+/// zstd's default level. Higher levels are much slower for little gain on
+/// hashes and addresses.
+pub const DEFAULT_LEVEL: i32 = 3;
+
+/// Largest blob `decode` inflates, well past the allocations of 100M wallets.
+pub const MAX_DECODED_LEN: u64 = 16 << 30;
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn compress(data: &[u8], level: i32) -> anyhow::Result<Vec<u8>> {
+    let frame = zstd::bulk::compress(data, level).context("Failed to compress blob")?;
+    let mut out = Vec::with_capacity(HEADER_LEN + frame.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.extend_from_slice(&frame);
+    Ok(out)
+}
+
+/// The uncompressed blob, borrowed as-is when it wasn't compressed.
+pub fn decode(data: &[u8]) -> anyhow::Result<Cow<'_, [u8]>> {
+    if !is_compressed(data) {
+        return Ok(Cow::Borrowed(data));
+    }
+    if data.len() < HEADER_LEN {
+        return Err(anyhow!("Truncated compressed blob"));
+    }
+    let len = u64::from_le_bytes(data[4..HEADER_LEN].try_into()?);
+    if len > MAX_DECODED_LEN {
+        return Err(anyhow!(
+            "Compressed blob claims {} bytes, over the {} byte limit",
+            len,
+            MAX_DECODED_LEN
+        ));
+    }
+    // The length comes from whoever uploaded the blob, so the buffer grows
+    // with the output rather than being allocated up front
+    let decoder = zstd::stream::read::Decoder::new(&data[HEADER_LEN..])
+        .context("Failed to decompress blob")?;
+    let mut out = Vec::with_capacity((len as usize).min(data.len().saturating_mul(8)));
+    decoder
+        .take(len + 1)
+        .read_to_end(&mut out)
+        .context("Failed to decompress blob")?;
+    if out.len() as u64 != len {
+        return Err(anyhow!(
+            "Decompressed blob is {} bytes, expected {}",
+            out.len(),
+            len
+        ));
+    }
+    Ok(Cow::Owned(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress() {
+        let data: Vec<u8> = (0..10_000u32).flat_map(|i| (i % 7).to_le_bytes()).collect();
+        let compressed = compress(&data, DEFAULT_LEVEL).unwrap();
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < data.len());
+        assert_eq!(decode(&compressed).unwrap(), data.as_slice());
+
+        // Uncompressed blobs pass through
+        assert!(matches!(decode(b"0x1,100").unwrap(), Cow::Borrowed(_)));
+
+        let mut truncated = compressed.clone();
+        truncated.truncate(compressed.len() / 2);
+        assert!(decode(&truncated).is_err());
+
+        // Lengths in the header are checked, not trusted
+        let mut huge = compressed.clone();
+        huge[4..HEADER_LEN].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(decode(&huge).is_err());
+        let mut short = compressed.clone();
+        short[4..HEADER_LEN].copy_from_slice(&100u64.to_le_bytes());
+        assert!(decode(&short).is_err());
+        let mut long = compressed.clone();
+        long[4..HEADER_LEN].copy_from_slice(&(data.len() as u64 + 1).to_le_bytes());
+        assert!(decode(&long).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+use crate::cache::{BlobCache, DEFAULT_CACHE_SIZE_MB};
+use crate::network::{Network, NetworkConfig};
+use crate::signer::SignerKind;
+use anyhow::{anyhow, Context};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use sui_sdk_types::Address;
+use url::Url;
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Storage epochs for new campaign blobs when nothing else is set.
+pub const DEFAULT_EPOCHS: u32 = 4;
+
+/// Settings for one profile. Anything left unset falls back to the network's
+/// built-in defaults.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Profile {
+    pub network: Option<Network>,
+    pub graphql_url: Option<Url>,
+    pub package_id: Option<Address>,
+    pub latest_package_id: Option<Address>,
+    pub walrus_publisher: Option<Url>,
+    pub walrus_aggregator: Option<Url>,
+    pub epochs: Option<u32>,
+    pub gas_budget: Option<u64>,
+    pub gas_price: Option<u64>,
+    pub cache_dir: Option<PathBuf>,
+    pub cache_size_mb: Option<u64>,
+    pub signer: Option<SignerKind>,
+    /// Signing service for the `remote` signer.
+    pub signer_url: Option<Url>,
+    /// Wallet the `remote` signer signs as, instead of asking the service.
+    pub signer_address: Option<Address>,
+}
+
+impl Profile {
+    pub const KEYS: [&'static str; 14] = [
+        "network",
+        "graphql_url",
+        "package_id",
+        "latest_package_id",
+        "walrus_publisher",
+        "walrus_aggregator",
+        "epochs",
+        "gas_budget",
+        "gas_price",
+        "cache_dir",
+        "cache_size_mb",
+        "signer",
+        "signer_url",
+        "signer_address",
+    ];
+
+    /// `LARGE_NETWORK`, `LARGE_EPOCHS`, `LARGE_GAS_BUDGET` and so on, one
+    /// variable per key.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    fn from_vars(vars: impl Iterator<Item = (String, String)>) -> anyhow::Result<Self> {
+        envy::prefixed("LARGE_")
+            .from_iter(vars)
+            .context("Invalid LARGE_* environment variable")
+    }
+
+    /// Fields set in `other` take precedence.
+    pub fn merge(self, other: Profile) -> Profile {
+        Profile {
+            network: other.network.or(self.network),
+            graphql_url: other.graphql_url.or(self.graphql_url),
+            package_id: other.package_id.or(self.package_id),
+            latest_package_id: other.latest_package_id.or(self.latest_package_id),
+            walrus_publisher: other.walrus_publisher.or(self.walrus_publisher),
+            walrus_aggregator: other.walrus_aggregator.or(self.walrus_aggregator),
+            epochs: other.epochs.or(self.epochs),
+            gas_budget: other.gas_budget.or(self.gas_budget),
+            gas_price: other.gas_price.or(self.gas_price),
+            cache_dir: other.cache_dir.or(self.cache_dir),
+            cache_size_mb: other.cache_size_mb.or(self.cache_size_mb),
+            signer: other.signer.or(self.signer),
+            signer_url: other.signer_url.or(self.signer_url),
+            signer_address: other.signer_address.or(self.signer_address),
+        }
+    }
+
+    pub fn epochs(&self) -> u32 {
+        self.epochs.unwrap_or(DEFAULT_EPOCHS)
+    }
+
+    /// The blob cache, in `~/.cache/large` unless `cache_dir` is set.
+    pub fn blob_cache(&self) -> anyhow::Result<BlobCache> {
+        let dir = match &self.cache_dir {
+            Some(dir) => dir.clone(),
+            None => BlobCache::default_dir()?,
+        };
+        let size_mb = self.cache_size_mb.unwrap_or(DEFAULT_CACHE_SIZE_MB);
+        Ok(BlobCache::new(dir, size_mb * 1024 * 1024))
+    }
+
+    /// The network table for `network`, with this profile's overrides.
+    pub fn network_config(&self, network: Network) -> NetworkConfig {
+        let mut config = NetworkConfig::new(network);
+        if let Some(url) = &self.graphql_url {
+            config.graphql_url = url.clone();
+        }
+        if let Some(package_id) = self.package_id {
+            config.package_id = Some(package_id);
+        }
+        if let Some(package_id) = self.latest_package_id {
+            config.latest_package_id = Some(package_id);
+        }
+        if let Some(url) = &self.walrus_publisher {
+            config.walrus_publisher = Some(url.clone());
+        }
+        if let Some(url) = &self.walrus_aggregator {
+            config.walrus_aggregator = Some(url.clone());
+        }
+        if let Some(budget) = self.gas_budget {
+            config.gas.budget = budget;
+        }
+        if let Some(price) = self.gas_price {
+            config.gas.price = price;
+        }
+        config
+    }
+
+    /// Set a key from its string form, checking that the value parses.
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        let mut table = self.to_table()?;
+        let value = match key {
+            "epochs" | "gas_budget" | "gas_price" | "cache_size_mb" => toml::Value::Integer(
+                value
+                    .parse()
+                    .with_context(|| format!("{} must be a number", key))?,
+            ),
+            _ => toml::Value::String(value.to_string()),
+        };
+        table.insert(Self::check_key(key)?.to_string(), value);
+        *self = table
+            .try_into()
+            .with_context(|| format!("Invalid value for {}", key))?;
+        Ok(())
+    }
+
+    pub fn unset(&mut self, key: &str) -> anyhow::Result<()> {
+        let mut table = self.to_table()?;
+        table.remove(Self::check_key(key)?);
+        *self = table.try_into()?;
+        Ok(())
+    }
+
+    fn check_key(key: &str) -> anyhow::Result<&str> {
+        Self::KEYS
+            .iter()
+            .find(|k| **k == key)
+            .copied()
+            .ok_or(anyhow!(
+                "Unknown config key: {}, expected one of: {}",
+                key,
+                Self::KEYS.join(", ")
+            ))
+    }
+
+    fn to_table(&self) -> anyhow::Result<toml::Table> {
+        Ok(toml::Table::try_from(self)?)
+    }
+}
+
+/// `config.toml`: a table of named profiles.
+///
+/// ```toml
+/// [profiles.staging]
+/// network = "testnet"
+/// epochs = 2
+///
+/// [profiles.production]
+/// network = "mainnet"
+/// epochs = 53
+/// gas_budget = 10000000
+/// ```
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct ConfigFile {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl ConfigFile {
+    /// `$LARGE_CONFIG`, falling back to `~/.config/large/config.toml`.
+    pub fn path() -> anyhow::Result<PathBuf> {
+        if let Some(path) = std::env::var_os("LARGE_CONFIG") {
+            return Ok(PathBuf::from(path));
+        }
+        let home = dirs::home_dir().ok_or(anyhow!("No home directory found"))?;
+        Ok(home.join(".config").join("large").join("config.toml"))
+    }
+
+    /// A missing file is an empty config.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read: {}", path.display())),
+        };
+        toml::from_str(&data).with_context(|| format!("Malformed config: {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context("Failed to create config directory")?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write: {}", path.display()))
+    }
+
+    /// The named profile. Only the default profile may be missing.
+    pub fn profile(&self, name: &str) -> anyhow::Result<Profile> {
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile.clone()),
+            None if name == DEFAULT_PROFILE => Ok(Profile::default()),
+            None => Err(anyhow!("No profile named {} in config", name)),
+        }
+    }
+}
+
+/// The named profile from the config file, with `LARGE_*` overrides applied.
+pub fn load_profile(name: &str) -> anyhow::Result<Profile> {
+    let file = ConfigFile::load(&ConfigFile::path()?)?;
+    Ok(file.profile(name)?.merge(Profile::from_env()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large").join("config.toml");
+        assert!(ConfigFile::load(&path).unwrap().profiles.is_empty());
+
+        let mut staging = Profile::default();
+        staging.set("network", "testnet").unwrap();
+        staging.set("epochs", "2").unwrap();
+        staging.set("gas_budget", "9000000").unwrap();
+        staging.set("signer", "sui-cli").unwrap();
+        assert_eq!(staging.signer, Some(SignerKind::SuiCli));
+        assert!(staging.set("signer", "ledger").is_err());
+        assert!(staging.set("epochs", "many").is_err());
+        assert!(staging.set("network", "moonnet").is_err());
+        assert!(staging.set("colour", "blue").is_err());
+
+        let mut file = ConfigFile::default();
+        file.profiles.insert("staging".into(), staging.clone());
+        file.save(&path).unwrap();
+
+        let loaded = ConfigFile::load(&path).unwrap();
+        assert_eq!(loaded.profile("staging").unwrap(), staging);
+        assert_eq!(loaded.profile(DEFAULT_PROFILE).unwrap(), Profile::default());
+        assert!(loaded.profile("production").is_err());
+
+        staging.unset("gas_budget").unwrap();
+        assert_eq!(staging.gas_budget, None);
+        assert_eq!(staging.epochs(), 2);
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let vars = vec![
+            ("LARGE_NETWORK".to_string(), "mainnet".to_string()),
+            ("LARGE_GAS_PRICE".to_string(), "750".to_string()),
+            ("LARGE_LATEST_PACKAGE_ID".to_string(), "0x2".to_string()),
+            ("LARGE_SIGNER".to_string(), "remote".to_string()),
+            (
+                "LARGE_SIGNER_URL".to_string(),
+                "https://signer.example/v1".to_string(),
+            ),
+            ("LARGE_PRIVATE_KEY".to_string(), "ignored".to_string()),
+        ];
+        let env = Profile::from_vars(vars.into_iter()).unwrap();
+        assert_eq!(env.network, Some(Network::Mainnet));
+
+        let file = Profile {
+            network: Some(Network::Testnet),
+            gas_price: Some(1_000),
+            gas_budget: Some(8_000_000),
+            signer: Some(SignerKind::Keystore),
+            ..Default::default()
+        };
+        let merged = file.merge(env);
+        assert_eq!(merged.network, Some(Network::Mainnet));
+        assert_eq!(merged.signer, Some(SignerKind::Remote));
+        assert!(merged.signer_url.is_some());
+        assert_eq!(merged.epochs(), DEFAULT_EPOCHS);
+
+        let net = merged.network_config(merged.network.unwrap());
+        assert_eq!(net.gas.price, 750);
+        assert_eq!(net.gas.budget, 8_000_000);
+        assert!(net.package_id().is_ok());
+        assert_eq!(net.call_package_id().unwrap(), Address::TWO);
+
+        let bad = vec![("LARGE_EPOCHS".to_string(), "soon".to_string())];
+        assert!(Profile::from_vars(bad.into_iter()).is_err());
+    }
+}
@@ -94,7 +94,11 @@ pub async fn sign_tx(
     Ok(sig)
 }
 
-pub async fn write_files(files: Vec<String>, epochs: u32) -> anyhow::Result<Vec<NewBlob>> {
+pub async fn write_files(
+    files: Vec<String>,
+    epochs: u32,
+    deletable: bool,
+) -> anyhow::Result<Vec<NewBlob>> {
     let file_paths = files
         .iter()
         .map(|f| format!("\"{}\"", f))
@@ -108,12 +112,12 @@ pub async fn write_files(files: Vec<String>, epochs: u32) -> anyhow::Result<Vec<
                 "store": {{
                     "files": [{}],
                     "epochs": {},
-                    "deletable": true
+                    "deletable": {}
                 }}
             }}
         }}
         "#,
-        file_paths, epochs
+        file_paths, epochs, deletable
     );
 
     let output = Command::new("walrus")
@@ -144,7 +148,11 @@ pub async fn write_files(files: Vec<String>, epochs: u32) -> anyhow::Result<Vec<
     Ok(blobs)
 }
 
-pub async fn write_blobs(values: Vec<&[u8]>, epochs: u32) -> anyhow::Result<Vec<NewBlob>> {
+pub async fn write_blobs(
+    values: Vec<&[u8]>,
+    epochs: u32,
+    deletable: bool,
+) -> anyhow::Result<Vec<NewBlob>> {
     // Keep temp_file in scope to prevent deletion
     let mut temp_files: Vec<NamedTempFile> = Vec::new();
     let mut temp_file_paths: Vec<String> = Vec::new();
@@ -168,7 +176,7 @@ pub async fn write_blobs(values: Vec<&[u8]>, epochs: u32) -> anyhow::Result<Vec<
         temp_files.push(temp_file);
     }
 
-    let blob_ids = write_files(temp_file_paths, epochs).await?;
+    let blob_ids = write_files(temp_file_paths, epochs, deletable).await?;
 
     Ok(blob_ids)
 }
@@ -207,6 +215,58 @@ pub async fn read_blob(id: &str) -> anyhow::Result<Blob> {
     Ok(json)
 }
 
+pub async fn extend_blob(object: &Address, epochs: u32) -> anyhow::Result<()> {
+    let json_input = format!(
+        r#"
+        {{
+            "command": {{
+                "extend": {{
+                    "blobObjId": "{}",
+                    "epochsExtended": {}
+                }}
+            }}
+        }}
+    "#,
+        object, epochs
+    );
+
+    run_walrus_json(&json_input).await?;
+
+    Ok(())
+}
+
+pub async fn delete_blob(object: &Address) -> anyhow::Result<()> {
+    let json_input = format!(
+        r#"
+        {{
+            "command": {{
+                "delete": {{
+                    "objectIds": ["{}"],
+                    "yes": true
+                }}
+            }}
+        }}
+    "#,
+        object
+    );
+
+    run_walrus_json(&json_input).await?;
+
+    Ok(())
+}
+
+async fn run_walrus_json(json_input: &str) -> anyhow::Result<String> {
+    let output = Command::new("walrus")
+        .arg("json")
+        .arg(json_input)
+        .arg("--json")
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to execute walrus command: {}", e))?;
+
+    parse_terminal_output(&output)
+}
+
 fn reorder_results(results: &mut [BlobStoreResult], paths: &[String]) {
     results.sort_by(|a, b| {
         let index_a = paths.iter().position(|p| p == &a.path).unwrap();
@@ -219,11 +279,22 @@ fn reorder_results(results: &mut [BlobStoreResult], paths: &[String]) {
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct Blob {
-    pub blob: String,
+    #[serde(deserialize_with = "decode_base64")]
+    pub blob: Vec<u8>,
     pub blob_id: String,
 }
 
-#[derive(serde::Deserialize, Debug)]
+fn decode_base64<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: String = serde::Deserialize::deserialize(deserializer)?;
+    base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(serde::de::Error::custom)
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct NewBlob {
@@ -0,0 +1,309 @@
+use anyhow::{anyhow, Context};
+use base64::Engine;
+use std::path::{Path, PathBuf};
+use sui_crypto::ed25519::Ed25519PrivateKey;
+use sui_crypto::secp256k1::Secp256k1PrivateKey;
+use sui_crypto::secp256r1::Secp256r1PrivateKey;
+use sui_crypto::SuiSigner;
+use sui_sdk_types::{Address, SignatureScheme, Transaction, UserSignature};
+
+/// A private key as stored by the Sui CLI: a scheme flag byte followed by the
+/// 32 byte secret.
+pub enum PrivateKey {
+    Ed25519(Ed25519PrivateKey),
+    Secp256k1(Secp256k1PrivateKey),
+    Secp256r1(Secp256r1PrivateKey),
+}
+
+impl PrivateKey {
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let (flag, secret) = bytes.split_first().ok_or(anyhow!("empty private key"))?;
+        let secret: [u8; 32] = secret
+            .try_into()
+            .map_err(|_| anyhow!("invalid private key length"))?;
+
+        let key = match SignatureScheme::from_byte(*flag).map_err(|e| anyhow!("{:?}", e))? {
+            SignatureScheme::Ed25519 => Self::Ed25519(Ed25519PrivateKey::new(secret)),
+            SignatureScheme::Secp256k1 => Self::Secp256k1(Secp256k1PrivateKey::new(secret)?),
+            SignatureScheme::Secp256r1 => Self::Secp256r1(Secp256r1PrivateKey::new(secret)),
+            scheme => return Err(anyhow!("unsupported key scheme: {}", scheme.name())),
+        };
+
+        Ok(key)
+    }
+
+    /// Parse a Bech32 `suiprivkey...` string, as exported by `sui keytool export`.
+    pub fn from_suiprivkey(value: &str) -> anyhow::Result<Self> {
+        let (hrp, data) = bech32::decode(value.trim())?;
+        if hrp.as_str() != "suiprivkey" {
+            return Err(anyhow!("not a suiprivkey string"));
+        }
+        Self::from_bytes(&data)
+    }
+
+    pub fn address(&self) -> Address {
+        match self {
+            Self::Ed25519(key) => key.public_key().derive_address(),
+            Self::Secp256k1(key) => key.public_key().derive_address(),
+            Self::Secp256r1(key) => key.public_key().derive_address(),
+        }
+    }
+
+    pub fn sign_tx(&self, tx: &Transaction) -> anyhow::Result<UserSignature> {
+        let sig = match self {
+            Self::Ed25519(key) => key.sign_transaction(tx)?,
+            Self::Secp256k1(key) => key.sign_transaction(tx)?,
+            Self::Secp256r1(key) => key.sign_transaction(tx)?,
+        };
+        Ok(sig)
+    }
+}
+
+/// The keys in a `sui.keystore` file. Entries this tool can't sign with,
+/// such as zkLogin or passkey keys, are skipped rather than failing the load.
+pub struct Keystore {
+    keys: Vec<PrivateKey>,
+    skipped: usize,
+}
+
+impl Keystore {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read keystore: {}", path.display()))?;
+        let entries: Vec<String> = serde_json::from_str(&data).context("Malformed keystore")?;
+
+        let keys: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| {
+                let bts = base64::engine::general_purpose::STANDARD
+                    .decode(entry)
+                    .ok()?;
+                PrivateKey::from_bytes(&bts).ok()
+            })
+            .collect();
+        let skipped = entries.len() - keys.len();
+
+        Ok(Self { keys, skipped })
+    }
+
+    pub fn get(&self, wallet: &Address) -> Option<&PrivateKey> {
+        self.keys.iter().find(|key| key.address() == *wallet)
+    }
+
+    pub fn addresses(&self) -> Vec<Address> {
+        self.keys.iter().map(PrivateKey::address).collect()
+    }
+
+    pub fn sign_tx(&self, wallet: &Address, tx: &Transaction) -> anyhow::Result<UserSignature> {
+        let key = self.get(wallet).ok_or_else(|| self.missing(wallet))?;
+        key.sign_tx(tx)
+    }
+
+    /// No usable key for `wallet`, noting any entries that were skipped.
+    pub(crate) fn missing(&self, wallet: &Address) -> anyhow::Error {
+        if self.skipped == 0 {
+            return anyhow!("No key found in keystore for: {}", wallet);
+        }
+        anyhow!(
+            "No usable key found in keystore for: {}, {} entries it can't sign with were skipped",
+            wallet,
+            self.skipped
+        )
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct SuiEnv {
+    pub alias: String,
+    pub rpc: String,
+}
+
+/// The parts of the Sui CLI `client.yaml` that this tool uses.
+#[derive(Debug)]
+pub struct SuiConfig {
+    pub keystore: PathBuf,
+    pub envs: Vec<SuiEnv>,
+    pub active_env: Option<String>,
+    pub active_address: Option<Address>,
+}
+
+impl SuiConfig {
+    pub fn load() -> anyhow::Result<Self> {
+        Self::load_from(&sui_config_dir()?.join("client.yaml"))
+    }
+
+    pub fn load_from(path: &Path) -> anyhow::Result<Self> {
+        #[derive(serde::Deserialize)]
+        struct ClientYaml {
+            keystore: KeystoreLocation,
+            #[serde(default)]
+            envs: Vec<SuiEnv>,
+            active_env: Option<String>,
+            active_address: Option<String>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct KeystoreLocation {
+            #[serde(rename = "File")]
+            file: Option<PathBuf>,
+        }
+
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("No Sui client config found at: {}", path.display()))?;
+        let yaml: ClientYaml = serde_yaml::from_str(&data).context("Malformed client.yaml")?;
+
+        let keystore = yaml
+            .keystore
+            .file
+            .ok_or(anyhow!("Only file keystores are supported"))?;
+        let active_address = yaml.active_address.map(|addr| addr.parse()).transpose()?;
+
+        Ok(Self {
+            keystore,
+            envs: yaml.envs,
+            active_env: yaml.active_env,
+            active_address,
+        })
+    }
+
+    pub fn active_wallet(&self) -> anyhow::Result<Address> {
+        self.active_address
+            .ok_or(anyhow!("No active address set in Sui client config"))
+    }
+
+    pub fn active_rpc(&self) -> Option<url::Url> {
+        let alias = self.active_env.as_ref()?;
+        let env = self.envs.iter().find(|env| env.alias == *alias)?;
+        url::Url::parse(&env.rpc).ok()
+    }
+
+    pub fn load_keystore(&self) -> anyhow::Result<Keystore> {
+        Keystore::load(&self.keystore)
+    }
+}
+
+/// `$SUI_CONFIG_DIR`, falling back to `~/.sui/sui_config`.
+pub fn sui_config_dir() -> anyhow::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("SUI_CONFIG_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    let home = dirs::home_dir().ok_or(anyhow!("No home directory found"))?;
+    Ok(home.join(".sui").join("sui_config"))
+}
+
+/// The active address from the Sui client config, without the Sui CLI.
+pub fn current_wallet() -> anyhow::Result<Address> {
+    SuiConfig::load()?.active_wallet()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use sui_crypto::SuiVerifier;
+    use sui_sdk_types::ObjectDigest;
+    use sui_transaction_builder::{unresolved::Input, TransactionBuilder};
+
+    pub(crate) fn test_tx(sender: Address) -> Transaction {
+        let mut builder = TransactionBuilder::new();
+        let gas = Input::by_id(Address::new([9; 32]).into())
+            .with_owned_kind()
+            .with_version(1)
+            .with_digest(ObjectDigest::new([1; 32]));
+        builder.set_sender(sender);
+        builder.add_gas_objects(vec![gas]);
+        builder.set_gas_budget(1_000);
+        builder.set_gas_price(1_000);
+        let recipient = builder.input(sui_transaction_builder::Serialized(&sender));
+        let coin = builder.gas();
+        builder.transfer_objects(vec![coin], recipient);
+        builder.finish().unwrap()
+    }
+
+    fn write_config(dir: &Path, keys: &[Vec<u8>], active: &Address) -> PathBuf {
+        let keystore = dir.join("sui.keystore");
+        let entries: Vec<_> = keys
+            .iter()
+            .map(|k| base64::engine::general_purpose::STANDARD.encode(k))
+            .collect();
+        std::fs::write(&keystore, serde_json::to_string(&entries).unwrap()).unwrap();
+
+        let client = dir.join("client.yaml");
+        let yaml = format!(
+            "---\nkeystore:\n  File: {}\nenvs:\n  - alias: testnet\n    rpc: \"https://fullnode.testnet.sui.io:443\"\n    ws: ~\n    basic_auth: ~\nactive_env: testnet\nactive_address: \"{}\"\n",
+            keystore.display(),
+            active
+        );
+        std::fs::write(&client, yaml).unwrap();
+        client
+    }
+
+    #[test]
+    fn test_keystore_signing() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys: Vec<Vec<u8>> = (0..3u8)
+            .map(|flag| {
+                let mut k = vec![flag];
+                k.extend_from_slice(&[flag + 7; 32]);
+                k
+            })
+            .collect();
+        let addresses: Vec<_> = keys
+            .iter()
+            .map(|k| PrivateKey::from_bytes(k).unwrap().address())
+            .collect();
+
+        let config = SuiConfig::load_from(&write_config(dir.path(), &keys, &addresses[1])).unwrap();
+        assert_eq!(config.active_wallet().unwrap(), addresses[1]);
+        assert_eq!(config.active_env.as_deref(), Some("testnet"));
+        assert_eq!(config.envs[0].alias, "testnet");
+        assert_eq!(
+            config.active_rpc().unwrap().as_str(),
+            "https://fullnode.testnet.sui.io/"
+        );
+
+        let keystore = config.load_keystore().unwrap();
+        assert_eq!(keystore.addresses(), addresses);
+
+        for (key, addr) in keys.iter().zip(&addresses) {
+            let tx = test_tx(*addr);
+            let sig = keystore.sign_tx(addr, &tx).unwrap();
+            let verified = match PrivateKey::from_bytes(key).unwrap() {
+                PrivateKey::Ed25519(k) => k.verifying_key().verify_transaction(&tx, &sig),
+                PrivateKey::Secp256k1(k) => k.verifying_key().verify_transaction(&tx, &sig),
+                PrivateKey::Secp256r1(k) => k.verifying_key().verify_transaction(&tx, &sig),
+            };
+            assert!(verified.is_ok());
+        }
+
+        assert!(keystore
+            .sign_tx(&Address::new([0; 32]), &test_tx(addresses[0]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_mixed_keystore() {
+        let dir = tempfile::tempdir().unwrap();
+        let ed25519 = [[0].as_slice(), &[4; 32]].concat();
+        let address = PrivateKey::from_bytes(&ed25519).unwrap().address();
+        // A zkLogin entry and a passkey entry around a usable key
+        let keys = vec![vec![5; 40], ed25519, [[6].as_slice(), &[1; 32]].concat()];
+
+        let config = SuiConfig::load_from(&write_config(dir.path(), &keys, &address)).unwrap();
+        let keystore = config.load_keystore().unwrap();
+        assert_eq!(keystore.addresses(), vec![address]);
+        assert!(keystore.sign_tx(&address, &test_tx(address)).is_ok());
+
+        let other = Address::new([1; 32]);
+        let err = keystore.sign_tx(&other, &test_tx(other)).unwrap_err();
+        assert!(err.to_string().contains("2 entries"));
+    }
+
+    #[test]
+    fn test_suiprivkey() {
+        let encoded = crate::sui::suiprivkey_from_bytes(&[3; 32]).unwrap();
+        let key = PrivateKey::from_suiprivkey(&encoded).unwrap();
+        let expected = PrivateKey::from_bytes(&[[0].as_slice(), &[3; 32]].concat()).unwrap();
+        assert_eq!(key.address(), expected.address());
+        assert!(PrivateKey::from_suiprivkey("not a key").is_err());
+    }
+}
@@ -1,64 +1,339 @@
+pub mod allocations;
+pub mod audit;
+pub mod builder;
+pub mod cache;
+pub mod campaign;
+pub mod claim;
+pub mod compress;
+pub mod config;
 pub mod ffi;
+pub mod keystore;
 pub mod merkle;
+pub mod network;
+pub mod shards;
+pub mod signer;
+pub mod store;
 pub mod sui;
 pub mod txns;
 pub mod wallets;
+pub mod walrus;
 
-use base64::Engine;
+use bytes::Bytes;
 use futures::StreamExt;
 use std::str::FromStr;
+use store::BlobStore;
 use sui_sdk_types::Address;
 
 pub fn sui_coin() -> sui_sdk_types::TypeTag {
     sui_sdk_types::TypeTag::from_str("0x2::coin::Coin<0x2::sui::SUI>").unwrap()
 }
 
-pub fn package_id() -> sui_sdk_types::Address {
-    // TESTNET
-    sui_sdk_types::Address::from_str(
-        "0x5cccbfec0ef491993f5b2aa19b98845476ad720254c2e758254e23dbe547b94d",
-    )
-    .unwrap()
+pub async fn fetch_merkle_tree(
+    client: &sui_graphql_client::Client,
+    store: &dyn BlobStore,
+    object: &Address,
+) -> anyhow::Result<merkle::MerkleTree> {
+    let blob_id = resolve_blob_id(client, store, object).await?;
+    fetch_merkle_tree_blob(store, &blob_id).await
 }
 
-pub fn drop_object() -> sui_sdk_types::ObjectId {
-    // A pre-created testnet campaign
-    sui_sdk_types::ObjectId::from_str(
-        "0xdda2402ee7e7a4cb0a5a68692e9dac087be029bbd7d518e189121387a12b71b1",
-    )
-    .unwrap()
+pub async fn fetch_merkle_tree_blob(
+    store: &dyn BlobStore,
+    blob_id: &str,
+) -> anyhow::Result<merkle::MerkleTree> {
+    let data = store.get(blob_id).await?;
+    let data = compress::decode(&data)?;
+    // Campaigns created before the sharded format store the BCS tree
+    if shards::is_sharded(&data) {
+        return shards::decode(&data);
+    }
+    let out = bcs::from_bytes(&data)?;
+    Ok(out)
 }
 
-pub async fn fetch_merkle_tree(
+/// Just the proof for one leaf. Sharded tree blobs are range read, so only
+/// the shards on the leaf's path are downloaded. Other tree blobs, or stores
+/// that can't read ranges, download the whole tree.
+pub async fn fetch_proof(
     client: &sui_graphql_client::Client,
+    store: &dyn BlobStore,
     object: &Address,
-) -> anyhow::Result<merkle::MerkleTree> {
-    let blob_id = sui::get_blob_from_obj(client, object).await?;
-    fetch_merkle_tree_blob(&blob_id).await
+    leaf_index: u64,
+) -> anyhow::Result<merkle::LeafProof> {
+    let blob_id = resolve_blob_id(client, store, object).await?;
+    fetch_proof_blob(store, &blob_id, leaf_index).await
 }
 
-pub async fn fetch_merkle_tree_blob(blob_id: &str) -> anyhow::Result<merkle::MerkleTree> {
-    let data = ffi::read_blob(blob_id).await?;
-    let bts = base64::engine::general_purpose::STANDARD.decode(data.blob)?;
-    let out = bcs::from_bytes(&bts)?;
-    Ok(out)
+pub async fn fetch_proof_blob(
+    store: &dyn BlobStore,
+    blob_id: &str,
+    leaf_index: u64,
+) -> anyhow::Result<merkle::LeafProof> {
+    if let Some(manifest) = read_manifest(store, blob_id).await? {
+        return shards::read_proof(store, blob_id, &manifest, leaf_index).await;
+    }
+    let tree = fetch_merkle_tree_blob(store, blob_id).await?;
+    tree.leaf_proof(leaf_index).ok_or(anyhow::anyhow!(
+        "Leaf index {} is out of range, the tree has {} leaves",
+        leaf_index,
+        tree.leaf_count
+    ))
+}
+
+/// The manifest of a sharded tree blob, read on its own. `None` for other
+/// tree blobs, or stores that can't read ranges.
+async fn read_manifest(
+    store: &dyn BlobStore,
+    blob_id: &str,
+) -> anyhow::Result<Option<shards::Manifest>> {
+    if !store.supports_ranges() {
+        return Ok(None);
+    }
+    let head = store
+        .get_range(blob_id, 0..shards::MANIFEST_LEN as u64)
+        .await?;
+    if !shards::is_sharded(&head) {
+        return Ok(None);
+    }
+    Ok(Some(shards::Manifest::decode(&head)?))
+}
+
+/// A wallet's claim proof, checked against the onchain campaign. Reads the
+/// allocations and only the tree shards on the wallet's path, or rebuilds the
+/// tree for campaigns without a tree blob.
+pub async fn fetch_claim_proof(
+    client: &sui_graphql_client::Client,
+    store: &dyn BlobStore,
+    drop: &txns::Drop,
+    wallet: &Address,
+) -> anyhow::Result<claim::ClaimProof> {
+    let allocations = fetch_allocations(client, store, &drop.allocations).await?;
+    // Leaves are in the same order as the allocations
+    let leaf_index = allocations
+        .get_position(wallet)
+        .ok_or(claim::ClaimError::NoAllocation(*wallet))?;
+    let allocation = allocations[leaf_index].1;
+    let leaf_proof = match drop.tree_blob() {
+        Some(object) => fetch_proof(client, store, &object, leaf_index as u64).await?,
+        // Nothing more to download, the tree comes from the allocations
+        None => wallets::build_tree(&allocations, drop.tree_version)?
+            .leaf_proof(leaf_index as u64)
+            .ok_or(anyhow::anyhow!("Leaf index {} is out of range", leaf_index))?,
+    };
+    Ok(claim::ClaimProof::from_leaf_proof(
+        drop,
+        &leaf_proof,
+        wallet,
+        allocation,
+    )?)
+}
+
+/// A campaign's tree, for proving many wallets. Sharded tree blobs on stores
+/// that read ranges are left in the store and read a proof at a time.
+pub enum CampaignTree {
+    Whole(merkle::MerkleTree),
+    Sharded {
+        blob_id: String,
+        manifest: shards::Manifest,
+    },
+}
+
+/// The campaign's tree, checked against the `Drop`. Only the manifest is read
+/// from sharded tree blobs, campaigns without a tree blob have it rebuilt
+/// from `allocations`.
+pub async fn open_campaign_tree(
+    client: &sui_graphql_client::Client,
+    store: &dyn BlobStore,
+    drop: &txns::Drop,
+    allocations: &[(Address, u64)],
+) -> anyhow::Result<CampaignTree> {
+    let tree = match drop.tree_blob() {
+        Some(object) => {
+            let blob_id = resolve_blob_id(client, store, &object).await?;
+            if let Some(manifest) = read_manifest(store, &blob_id).await? {
+                claim::check_root(
+                    drop,
+                    manifest.tree_version,
+                    manifest.root,
+                    manifest.leaf_count,
+                )?;
+                return Ok(CampaignTree::Sharded { blob_id, manifest });
+            }
+            fetch_merkle_tree_blob(store, &blob_id).await?
+        }
+        None => wallets::build_tree(allocations, drop.tree_version)?,
+    };
+    claim::check_tree(drop, &tree)?;
+    Ok(CampaignTree::Whole(tree))
+}
+
+impl CampaignTree {
+    /// The claim for `wallets`, see `claim::MultiClaim::for_drop`.
+    pub async fn multi_claim(
+        &self,
+        store: &dyn BlobStore,
+        drop: &txns::Drop,
+        allocations: &Vec<(Address, u64)>,
+        wallets: &[Address],
+    ) -> anyhow::Result<claim::MultiClaim> {
+        let (blob_id, manifest) = match self {
+            CampaignTree::Whole(tree) => {
+                return Ok(claim::MultiClaim::for_drop(
+                    drop,
+                    tree,
+                    allocations,
+                    wallets,
+                )?)
+            }
+            CampaignTree::Sharded { blob_id, manifest } => (blob_id, manifest),
+        };
+        let positions = wallets
+            .iter()
+            .map(|wallet| {
+                allocations
+                    .get_position(wallet)
+                    .ok_or(claim::ClaimError::NoAllocation(*wallet))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let proofs = futures::future::try_join_all(
+            positions
+                .iter()
+                .map(|i| shards::read_proof(store, blob_id, manifest, *i as u64)),
+        )
+        .await?;
+        let rows: Vec<_> = positions.iter().map(|i| allocations[*i]).collect();
+        Ok(claim::MultiClaim::from_leaf_proofs(drop, &rows, &proofs)?)
+    }
 }
 
 pub async fn fetch_allocations(
     client: &sui_graphql_client::Client,
+    store: &dyn BlobStore,
     object: &Address,
 ) -> anyhow::Result<Vec<(Address, u64)>> {
-    let blob_id = sui::get_blob_from_obj(client, object).await?;
-    fetch_allocations_blob(&blob_id).await
+    let blob_id = resolve_blob_id(client, store, object).await?;
+    fetch_allocations_blob(store, &blob_id).await
 }
 
-pub async fn fetch_allocations_blob(blob_id: &str) -> anyhow::Result<Vec<(Address, u64)>> {
-    let data = ffi::read_blob(&blob_id).await?;
-    let bts = base64::engine::general_purpose::STANDARD.decode(data.blob)?;
-    let out = wallets::parse_csv_bytes(&bts)?;
+pub async fn fetch_allocations_blob(
+    store: &dyn BlobStore,
+    blob_id: &str,
+) -> anyhow::Result<Vec<(Address, u64)>> {
+    let data = store.get(blob_id).await?;
+    let data = compress::decode(&data)?;
+    // Campaigns created before the binary format store CSV
+    if allocations::is_binary(&data) {
+        return Ok(allocations::AllocationTable::parse(&data)?.to_vec());
+    }
+    let out = wallets::parse_csv_bytes(&data)?;
     Ok(out)
 }
 
+async fn resolve_blob_id(
+    client: &sui_graphql_client::Client,
+    store: &dyn BlobStore,
+    object: &Address,
+) -> anyhow::Result<String> {
+    match store.blob_id_for_object(object) {
+        Some(blob_id) => Ok(blob_id),
+        None => sui::get_blob_from_obj(client, object).await,
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct CampaignBlobs {
+    /// `None` when only the allocations are stored.
+    #[serde(default)]
+    pub merkle_tree: Option<ffi::NewBlob>,
+    pub allocations: ffi::NewBlob,
+}
+
+impl CampaignBlobs {
+    /// The `merkle_tree` address for the `Drop`, `@0x0` without a tree blob.
+    pub fn tree_object(&self) -> Address {
+        self.merkle_tree
+            .as_ref()
+            .map_or(Address::ZERO, |blob| blob.object_address)
+    }
+}
+
+/// Serialized campaign blobs, ready to upload.
+pub struct CampaignData {
+    /// `None` for campaigns that leave claimers to rebuild the tree from the
+    /// allocations. A tree built with a spill directory is mapped from its
+    /// file.
+    pub merkle_tree: Option<Bytes>,
+    pub allocations: Bytes,
+}
+
+impl CampaignData {
+    pub fn encode(
+        merkle_tree: &merkle::MerkleTree,
+        wallets: &[(Address, u64)],
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            merkle_tree: Some(shards::encode(merkle_tree, shards::DEFAULT_SHARD_HEIGHT)?.into()),
+            allocations: allocations::encode(wallets)?.into(),
+        })
+    }
+
+    /// Drop the tree blob, claims will rebuild the tree from the allocations.
+    pub fn without_tree(self) -> Self {
+        Self {
+            merkle_tree: None,
+            ..self
+        }
+    }
+
+    /// Only the allocations are compressed. The tree is hashes, which don't
+    /// compress, and it has to stay range readable.
+    pub fn compress(self, level: i32) -> anyhow::Result<Self> {
+        Ok(Self {
+            merkle_tree: self.merkle_tree,
+            allocations: compress::compress(&self.allocations, level)?.into(),
+        })
+    }
+
+    /// Tree and allocations sizes, the tree is 0 when it isn't stored.
+    pub fn sizes(&self) -> [u64; 2] {
+        [
+            self.merkle_tree
+                .as_ref()
+                .map_or(0, |tree| tree.len() as u64),
+            self.allocations.len() as u64,
+        ]
+    }
+}
+
+pub async fn write_campaign_blobs(
+    store: &dyn BlobStore,
+    data: &CampaignData,
+    epochs: u32,
+    deletable: bool,
+) -> anyhow::Result<CampaignBlobs> {
+    // Clones of `Bytes` share the data
+    let values = data
+        .merkle_tree
+        .iter()
+        .cloned()
+        .chain([data.allocations.clone()])
+        .collect();
+    let mut blobs = store.put_many(values, epochs, deletable).await?.into_iter();
+
+    let merkle_tree = match data.merkle_tree {
+        Some(_) => Some(blobs.next().ok_or(anyhow::anyhow!("missing merkle blob"))?),
+        None => None,
+    };
+    let allocations = blobs
+        .next()
+        .ok_or(anyhow::anyhow!("missing addresses blob"))?;
+
+    Ok(CampaignBlobs {
+        merkle_tree,
+        allocations,
+    })
+}
+
 pub async fn read_stream(response: reqwest::Response) -> anyhow::Result<Vec<u8>> {
     let mut stream = response.bytes_stream();
     let mut buffer = bytes::BytesMut::new();
@@ -73,17 +348,30 @@ pub async fn read_stream(response: reqwest::Response) -> anyhow::Result<Vec<u8>>
 
 //type Allocations = Vec<(Address, u64)>;
 
+/// Lookups on an allocations list. Rows are in leaf order, which is sorted by
+/// address for every list `wallets::clean_addresses` returns and every binary
+/// allocations blob, but not necessarily for legacy CSV blobs.
 pub trait AllocationExt {
     fn get_allocation(&self, wallet: &Address) -> Option<u64>;
-    fn get_leaf(&self, wallet: &Address) -> Option<merkle::Hash>;
+    /// Row of `wallet`, which is also its leaf index in the campaign's tree.
+    fn get_position(&self, wallet: &Address) -> Option<usize>;
+    fn get_leaf(&self, wallet: &Address, version: merkle::TreeVersion) -> Option<merkle::Hash>;
 }
 
 impl AllocationExt for Vec<(Address, u64)> {
     fn get_allocation(&self, wallet: &Address) -> Option<u64> {
-        self.iter().find(|(addr, _)| addr == wallet).map(|v| v.1)
+        self.get_position(wallet).map(|i| self[i].1)
+    }
+    fn get_position(&self, wallet: &Address) -> Option<usize> {
+        match self.binary_search_by(|(addr, _)| addr.cmp(wallet)) {
+            Ok(i) => Some(i),
+            // A miss is only final on a sorted list
+            Err(_) if self.is_sorted_by(|a, b| a.0 < b.0) => None,
+            Err(_) => self.iter().position(|(addr, _)| addr == wallet),
+        }
     }
-    fn get_leaf(&self, wallet: &Address) -> Option<merkle::Hash> {
+    fn get_leaf(&self, wallet: &Address, version: merkle::TreeVersion) -> Option<merkle::Hash> {
         let allo = self.get_allocation(wallet)?;
-        Some(wallets::hash_allo(wallet, allo))
+        Some(wallets::hash_leaf(version, wallet, allo))
     }
 }
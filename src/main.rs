@@ -1,10 +1,29 @@
+use anyhow::Context;
+use base64::Engine;
+use clap::builder::PossibleValuesParser;
 use clap::{Parser, Subcommand};
-use large::merkle::MerkleTree;
+use futures::{StreamExt, TryStreamExt};
+use large::builder::{TreeBuilder, TreeLevels};
+use large::cache::CachedStore;
+use large::campaign::CreateState;
+use large::config::{self, ConfigFile, Profile};
+use large::merkle::TreeVersion;
+use large::network::{Network, NetworkConfig};
+use large::signer::{CliSigner, KeySigner, KeystoreSigner, RemoteSigner, Signer, SignerKind};
+use large::store::{BlobStore, DirStore, WalrusCli};
 use large::sui;
-use large::{drop_object, ffi, txns, wallets, AllocationExt};
+use large::{audit, ffi, keystore, txns, wallets, walrus, AllocationExt};
 use spinners::{Spinner, Spinners};
+use std::collections::HashMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use sui_sdk_types::{Address, ObjectId, TypeTag};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use sui_sdk_types::{
+    Address, ExecutionStatus, ObjectId, TransactionDigest, TransactionEffects, TypeTag,
+};
+use url::Url;
 
 #[derive(Parser)]
 #[command(
@@ -13,15 +32,168 @@ use sui_sdk_types::{Address, ObjectId, TypeTag};
 
 Create low-cost airdrop campaigns to millions of users.
 
-Currently live on Sui Testnet.
+Live on Sui Mainnet and Testnet.
 
-This tool requires the Sui and Walrus CLIs to be installed."
+By default this tool signs with keys from the Sui CLI config in ~/.sui/sui_config."
 )]
 struct Cli {
+    #[clap(
+        long,
+        global = true,
+        help = "Settings profile from ~/.config/large/config.toml. Defaults to $LARGE_PROFILE, then \"default\""
+    )]
+    profile: Option<String>,
+    #[clap(
+        long,
+        global = true,
+        help = "Sui network: mainnet, testnet, devnet or localnet. Defaults to the active env in the Sui client config"
+    )]
+    network: Option<Network>,
+    #[clap(
+        long,
+        global = true,
+        help = "Custom Sui GraphQL endpoint, instead of the network's default"
+    )]
+    graphql_url: Option<Url>,
+    #[clap(
+        long,
+        global = true,
+        help = "Large package ID, instead of the network's default"
+    )]
+    package_id: Option<Address>,
+    #[clap(
+        long,
+        global = true,
+        help = "Upgraded Large package to call functions on, when it differs from the package ID"
+    )]
+    latest_package_id: Option<Address>,
+    #[clap(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = StoreKind::Walrus,
+        help = "Where campaign blobs are written to and read from"
+    )]
+    store: StoreKind,
+    #[clap(
+        long,
+        global = true,
+        default_value = ".large-blobs",
+        help = "Blob directory used with `--store dir`"
+    )]
+    blob_dir: PathBuf,
+    #[clap(
+        long,
+        global = true,
+        help = "Always download blobs instead of reading them from ~/.cache/large"
+    )]
+    no_cache: bool,
+    #[clap(
+        long,
+        global = true,
+        help = "How transactions are signed: keystore, sui-cli, env or remote. Defaults to the profile's signer, then keystore"
+    )]
+    signer: Option<SignerKind>,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum StoreKind {
+    /// Walrus publisher + aggregator over HTTP.
+    Walrus,
+    /// The Walrus CLI.
+    WalrusCli,
+    /// A local directory, for offline use.
+    Dir,
+}
+
+fn profile_name(cli: &Cli) -> String {
+    cli.profile
+        .clone()
+        .or_else(|| std::env::var("LARGE_PROFILE").ok())
+        .unwrap_or(config::DEFAULT_PROFILE.to_string())
+}
+
+// Flags win over the environment, which wins over the config file
+fn network_config(cli: &Cli, profile: &Profile) -> NetworkConfig {
+    let network = cli.network.or(profile.network).unwrap_or_else(|| {
+        keystore::SuiConfig::load()
+            .ok()
+            .and_then(|config| Network::from_sui_config(&config))
+            .unwrap_or(Network::Testnet)
+    });
+    let mut config = profile.network_config(network);
+    if let Some(url) = &cli.graphql_url {
+        config.graphql_url = url.clone();
+    }
+    if let Some(package_id) = cli.package_id {
+        config.package_id = Some(package_id);
+    }
+    if let Some(package_id) = cli.latest_package_id {
+        config.latest_package_id = Some(package_id);
+    }
+    config
+}
+
+fn blob_store(
+    cli: &Cli,
+    net: &NetworkConfig,
+    owner: Option<Address>,
+) -> anyhow::Result<Box<dyn BlobStore>> {
+    let store: Box<dyn BlobStore> = match cli.store {
+        StoreKind::Walrus => {
+            let walrus = net.walrus()?;
+            Box::new(match owner {
+                Some(owner) => walrus.send_object_to(owner),
+                None => walrus,
+            })
+        }
+        StoreKind::WalrusCli => Box::new(WalrusCli),
+        StoreKind::Dir => Box::new(DirStore::new(&cli.blob_dir)?),
+    };
+    Ok(store)
+}
+
+/// The store for reading campaign blobs. Downloads are cached on disk unless
+/// `--no-cache` is set, local directory stores are read directly.
+fn read_store(
+    cli: &Cli,
+    net: &NetworkConfig,
+    profile: &Profile,
+) -> anyhow::Result<Box<dyn BlobStore>> {
+    let store = blob_store(cli, net, None)?;
+    if cli.no_cache || matches!(cli.store, StoreKind::Dir) {
+        return Ok(store);
+    }
+    Ok(Box::new(CachedStore::new(store, profile.blob_cache()?)))
+}
+
+/// The flag's signer, then the profile's. The profile already has `LARGE_*`
+/// overrides applied.
+fn signer(cli: &Cli, profile: &Profile) -> anyhow::Result<Box<dyn Signer>> {
+    let signer: Box<dyn Signer> = match cli.signer.or(profile.signer).unwrap_or_default() {
+        SignerKind::Keystore => Box::new(KeystoreSigner::from_sui_config()?),
+        SignerKind::SuiCli => Box::new(CliSigner),
+        SignerKind::Env => Box::new(KeySigner::from_env()?),
+        SignerKind::Remote => {
+            let url = profile.signer_url.clone().ok_or(anyhow::anyhow!(
+                "No signing service set, set signer_url or LARGE_SIGNER_URL"
+            ))?;
+            let mut signer = RemoteSigner::new(url);
+            // Tokens are secrets, so they're only read from the environment
+            if let Ok(token) = std::env::var("LARGE_SIGNER_TOKEN") {
+                signer = signer.with_token(token);
+            }
+            if let Some(address) = profile.signer_address {
+                signer = signer.with_address(address);
+            }
+            Box::new(signer)
+        }
+    };
+    Ok(signer)
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// Create a new airdrop campaign.
@@ -29,135 +201,814 @@ enum Commands {
         #[clap(
             help = "Path to the CSV file containing the wallet addresses and token allocations"
         )]
-        path: String,
+        path: PathBuf,
+        #[clap(long, value_parser = parse_type_tag, help = "Coin type to airdrop, e.g. 0x2::sui::SUI")]
+        coin_type: Option<TypeTag>,
+        #[clap(
+            long,
+            help = "Walrus storage epochs for the campaign blobs. Defaults to the profile's setting"
+        )]
+        epochs: Option<u32>,
+        #[clap(
+            long,
+            conflicts_with = "permanent",
+            help = "Store the blobs as deletable (the default)"
+        )]
+        deletable: bool,
+        #[clap(long, help = "Store the blobs as permanent")]
+        permanent: bool,
+        #[clap(
+            long,
+            help = "Compress the allocations blob with zstd before upload. Older versions of this CLI can't read compressed campaigns"
+        )]
+        compress: bool,
+        #[clap(
+            long,
+            help = "Only upload the allocations, claims rebuild the merkle tree from them. Saves storing the tree, older versions of this CLI can't claim from these campaigns"
+        )]
+        no_tree_blob: bool,
+        #[clap(
+            long,
+            default_value = "1",
+            help = "Merkle tree format. 2 hashes leaves and nodes with distinct prefixes, and needs a package with `create_drop_v2`"
+        )]
+        tree_version: TreeVersion,
+        #[clap(long, short, help = "Skip the confirmation prompt")]
+        yes: bool,
+        #[command(flatten)]
+        build: BuildArgs,
+        #[clap(
+            long,
+            help = "Where progress is saved for `large resume`. Defaults to <PATH>.large-state.json"
+        )]
+        state: Option<PathBuf>,
+    },
+    /// Continue a campaign creation that stopped part way.
+    Resume {
+        #[clap(help = "State file written by `create-drop`")]
+        state: PathBuf,
+        #[command(flatten)]
+        build: BuildArgs,
+        #[clap(
+            long,
+            help = "Submit a new transaction even though the recorded one can't be found. Check the recorded digest first, or the campaign may be created twice"
+        )]
+        force_new_tx: bool,
     },
-    /// Print currently active wallet in Sui CLI.
+    /// Print the wallet of the selected signer.
     CurrentWallet,
-    /// Execute a claim with current wallet.
+    /// Execute a claim with the signer's wallet.
     Claim {
-        #[clap(help = "The object ID of the campaign you want to claim from")]
+        #[clap(
+            help = "The object ID of the campaign you want to claim from. Defaults to the network's demo campaign"
+        )]
+        drop_id: Option<ObjectId>,
+    },
+    /// Claim for every eligible wallet in a file with shared multi-proofs,
+    /// sending each wallet its allocation. Any signer can pay the gas.
+    ClaimMany {
+        #[clap(
+            long,
+            short,
+            help = "File with one address per line. Blank lines, # comments and extra CSV columns are ignored"
+        )]
+        input: PathBuf,
+        #[clap(
+            help = "The object ID of the campaign you want to claim from. Defaults to the network's demo campaign"
+        )]
         drop_id: Option<ObjectId>,
+        #[clap(long, short, help = "Skip the confirmation prompt")]
+        yes: bool,
+    },
+    /// Print a wallet's allocation, leaf index and merkle proof, for building
+    /// the claim transaction elsewhere.
+    Proof {
+        #[clap(help = "The wallet address to prove")]
+        wallet: Address,
+        #[clap(help = "The object ID of the campaign. Defaults to the network's demo campaign")]
+        drop_id: Option<ObjectId>,
+        #[clap(long, value_enum, default_value_t = ProofFormat::Json)]
+        format: ProofFormat,
     },
     /// Check any address for claim amount.
     CheckClaim {
+        #[clap(help = "The wallet address to check for a claim. Defaults to the signer's wallet")]
+        wallet: Option<Address>,
         #[clap(
-            help = "The wallet address to check for a claim. Defaults to active Sui CLI wallet"
+            help = "The object ID of the campaign you want to check. Defaults to the network's demo campaign"
         )]
-        wallet: Option<Address>,
+        drop_id: Option<ObjectId>,
+    },
+    /// Check a file of addresses for claim amounts and claim status, loading
+    /// the campaign once.
+    CheckClaims {
+        #[clap(
+            long,
+            short,
+            help = "File with one address per line. Blank lines, # comments and extra CSV columns are ignored"
+        )]
+        input: PathBuf,
         #[clap(
-            help = "The object ID of the campaign you want to check. Defaults to Testnet demo campaign"
+            help = "The object ID of the campaign you want to check. Defaults to the network's demo campaign"
         )]
         drop_id: Option<ObjectId>,
+        #[clap(long, value_enum, default_value_t = ReportFormat::Csv)]
+        format: ReportFormat,
+        #[clap(long, short, help = "Write the report to a file instead of stdout")]
+        output: Option<PathBuf>,
     },
-    /// Check that Sui + Walrus CLIs are installed.
+    /// Close a campaign owned by the signer's wallet and reclaim the unclaimed funds.
+    DeleteDrop {
+        #[clap(help = "The object ID of the campaign to close")]
+        drop_id: ObjectId,
+        #[clap(long, short, help = "Skip the confirmation prompt")]
+        yes: bool,
+    },
+    /// Audit a campaign: rebuild its tree from the allocations and check both
+    /// blobs against the onchain campaign.
+    VerifyDrop {
+        #[clap(help = "The object ID of the campaign. Defaults to the network's demo campaign")]
+        drop_id: Option<ObjectId>,
+    },
+    /// Show claim progress, remaining funds and blob storage of a campaign.
+    Status {
+        #[clap(help = "The object ID of the campaign. Defaults to the network's demo campaign")]
+        drop_id: Option<ObjectId>,
+        #[clap(
+            long,
+            default_value_t = 2,
+            help = "Warn when a blob expires within this many Walrus epochs"
+        )]
+        warn_epochs: u32,
+    },
+    /// List the campaigns a wallet can close, i.e. holds the DeleteCap for.
+    ListDrops {
+        #[clap(
+            long,
+            help = "Wallet that owns the campaigns. Defaults to the signer's wallet"
+        )]
+        owner: Option<Address>,
+        #[clap(long, help = "Print JSON instead of a table")]
+        json: bool,
+    },
+    /// Check the signer and which optional CLIs are installed.
     CheckEnv,
+    /// Show or edit the settings profiles in ~/.config/large/config.toml.
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+}
+
+/// How the merkle tree is built, for `create-drop` and `resume`.
+#[derive(clap::Args)]
+struct BuildArgs {
+    #[clap(
+        long,
+        help = "Threads for building the merkle tree. Defaults to one per core"
+    )]
+    build_threads: Option<usize>,
+    #[clap(
+        long,
+        help = "Build the merkle tree in a memory-mapped file in this directory, for lists too large to build in memory"
+    )]
+    spill_dir: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print the effective settings of the selected profile.
+    Show,
+    /// Set a key in the selected profile.
+    Set {
+        #[clap(value_parser = PossibleValuesParser::new(Profile::KEYS))]
+        key: String,
+        value: String,
+    },
+    /// Remove a key from the selected profile.
+    Unset {
+        #[clap(value_parser = PossibleValuesParser::new(Profile::KEYS))]
+        key: String,
+    },
+    /// List the profiles in the config file.
+    Profiles,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ProofFormat {
+    /// JSON, with hashes in hex and claim arguments in base64 BCS.
+    Json,
+    /// One `key: 0x...` line per value, with claim arguments in hex BCS.
+    Hex,
+}
+
+#[derive(serde::Serialize)]
+struct ProofOutput {
+    drop_id: ObjectId,
+    target: String,
+    type_arguments: Vec<String>,
+    wallet: Address,
+    allocation: u64,
+    leaf_index: u64,
+    root: String,
+    /// How the leaf and nodes are hashed.
+    tree_version: TreeVersion,
+    proof: Vec<String>,
+    claim_args: ClaimArgs,
+}
+
+/// Pure arguments of `drop::claim`, BCS encoded.
+#[derive(serde::Serialize)]
+struct ClaimArgs {
+    proof: String,
+    leaf_index: String,
+    allocation: String,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ReportFormat {
+    /// One row per wallet, with a header.
+    Csv,
+    /// An array of objects, one per wallet.
+    Json,
+}
+
+/// One row of the `check-claims` report.
+#[derive(serde::Serialize)]
+struct ClaimCheck {
+    wallet: Address,
+    allocation: u64,
+    amount: String,
+    status: &'static str,
+    claim_tx: Option<TransactionDigest>,
+    leaf_index: Option<u64>,
+}
+
+#[derive(serde::Serialize)]
+struct DropSummary {
+    drop_id: ObjectId,
+    coin_type: String,
+    symbol: String,
+    decimals: u8,
+    wallet_count: u32,
+    claimed: u64,
+    airdrop_total: u64,
+    vault: u64,
+}
+
+/// Ask before doing something that costs money or can't be undone. Without a
+/// terminal to ask on, `--yes` is required.
+fn confirm(question: &str, yes: bool) -> anyhow::Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(anyhow::anyhow!("Pass --yes to continue without a prompt"));
+    }
+    Ok(inquire::Confirm::new(question)
+        .with_default(false)
+        .prompt()?)
+}
+
+/// Wallets per `claim_many` transaction. Their proof nodes have to fit in
+/// one 16KB pure argument, and a wallet far from the others needs about 20.
+const CLAIM_BATCH: usize = 25;
+
+fn parse_type_tag(value: &str) -> Result<TypeTag, String> {
+    TypeTag::from_str(value).map_err(|e| format!("{:?}", e))
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+fn run_config(cli: &Cli, action: &ConfigAction) -> anyhow::Result<()> {
+    let path = ConfigFile::path()?;
+    let mut file = ConfigFile::load(&path)?;
+    let name = profile_name(cli);
+
+    match action {
+        ConfigAction::Show => {
+            let profile = file.profile(&name)?.merge(Profile::from_env()?);
+            let net = network_config(cli, &profile);
+            let show = |value: Option<String>| value.unwrap_or("(none)".to_string());
+            println!("Config file: {}", path.display());
+            println!("Profile: {}", name);
+            println!("network = {}", net.network);
+            println!("graphql_url = {}", net.graphql_url);
+            println!(
+                "package_id = {}",
+                show(net.package_id.map(|id| id.to_string()))
+            );
+            println!(
+                "latest_package_id = {}",
+                show(net.latest_package_id.map(|id| id.to_string()))
+            );
+            println!(
+                "walrus_publisher = {}",
+                show(net.walrus_publisher.map(|url| url.to_string()))
+            );
+            println!(
+                "walrus_aggregator = {}",
+                show(net.walrus_aggregator.map(|url| url.to_string()))
+            );
+            println!("epochs = {}", profile.epochs());
+            println!("gas_budget = {}", net.gas.budget);
+            println!("gas_price = {}", net.gas.price);
+            println!("cache_dir = {}", profile.blob_cache()?.dir().display());
+            println!(
+                "cache_size_mb = {}",
+                profile
+                    .cache_size_mb
+                    .unwrap_or(large::cache::DEFAULT_CACHE_SIZE_MB)
+            );
+            println!(
+                "signer = {}",
+                cli.signer.or(profile.signer).unwrap_or_default()
+            );
+            println!(
+                "signer_url = {}",
+                show(profile.signer_url.map(|url| url.to_string()))
+            );
+            println!(
+                "signer_address = {}",
+                show(profile.signer_address.map(|address| address.to_string()))
+            );
+        }
+        ConfigAction::Set { key, value } => {
+            file.profiles
+                .entry(name.clone())
+                .or_default()
+                .set(key, value)?;
+            file.save(&path)?;
+            println!("Set {} = {} in profile {}", key, value, name);
+        }
+        ConfigAction::Unset { key } => {
+            file.profiles
+                .get_mut(&name)
+                .ok_or(anyhow::anyhow!("No profile named {} in config", name))?
+                .unset(key)?;
+            file.save(&path)?;
+            println!("Unset {} in profile {}", key, name);
+        }
+        ConfigAction::Profiles => {
+            for profile in file.profiles.keys() {
+                let marker = if *profile == name { "*" } else { " " };
+                println!("{} {}", marker, profile);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The validated list, its tree and the encoded blobs.
+struct PreparedCampaign {
+    total: u64,
+    /// Blob sizes before compression.
+    raw_sizes: [u64; 2],
+    tree: TreeLevels,
+    data: large::CampaignData,
+}
+
+// Rebuilding is cheap next to uploading, so only the outcome of these stages
+// is recorded, and checked again on resume
+fn prepare_campaign(
+    state: &mut CreateState,
+    build: &BuildArgs,
+) -> anyhow::Result<PreparedCampaign> {
+    // Rows go straight into the allocations blob, which the tree is then
+    // built from, so the list is only held once
+    let csv = std::fs::File::open(&state.csv_path).context("Failed to open CSV file")?;
+    let (total, allocations) = large::allocations::encode_rows(wallets::csv_rows(csv))?;
+    let table = large::allocations::AllocationTable::parse(&allocations)?;
+    state.record_list(u32::try_from(table.len())?, total, &allocations)?;
+
+    let mut builder = TreeBuilder::new().version(state.tree_version);
+    if let Some(threads) = build.build_threads {
+        builder = builder.threads(threads);
+    }
+    if let Some(dir) = &build.spill_dir {
+        builder = builder.spill_to(dir);
+    }
+    // Progress comes from the worker threads, so only print when it moves on
+    let shown = AtomicU64::new(0);
+    let builder = builder.on_progress(move |progress| {
+        let percent = progress.done * 100 / progress.total;
+        if shown.fetch_max(percent, Ordering::Relaxed) < percent {
+            print!("\rBuilding merkle tree... {}%", percent);
+            let _ = std::io::stdout().flush();
+        }
+    });
+    print!("Building merkle tree...");
+    let tree = builder.build(table.len(), table.iter())?;
+    println!("\rBuilding merkle tree... done");
+    state.record_tree(&tree.root(), tree.leaf_count())?;
+
+    let data = large::CampaignData {
+        merkle_tree: if state.skip_tree {
+            None
+        } else {
+            let (levels, height) = (tree.levels(), large::shards::DEFAULT_SHARD_HEIGHT);
+            Some(match &build.spill_dir {
+                Some(dir) => bytes::Bytes::from_owner(large::shards::encode_levels_mapped(
+                    &levels,
+                    tree.version(),
+                    height,
+                    dir,
+                )?),
+                None => large::shards::encode_levels(&levels, tree.version(), height)?.into(),
+            })
+        },
+        allocations: allocations.into(),
+    };
+    let raw_sizes = data.sizes();
+    let data = if state.compress {
+        data.compress(large::compress::DEFAULT_LEVEL)?
+    } else {
+        data
+    };
+    Ok(PreparedCampaign {
+        total,
+        raw_sizes,
+        tree,
+        data,
+    })
+}
+
+async fn finish_campaign(
+    cli: &Cli,
+    net: &NetworkConfig,
+    client: &sui_graphql_client::Client,
+    signer: &dyn Signer,
+    state: &mut CreateState,
+    state_path: &Path,
+    campaign: &PreparedCampaign,
+) -> anyhow::Result<ObjectId> {
+    let wallet = signer.address().await?;
+
+    let blobs = match &state.blobs {
+        Some(blobs) => {
+            println!("Blobs already uploaded, skipping");
+            blobs.clone()
+        }
+        None => {
+            let store = blob_store(cli, net, Some(wallet))?;
+            println!("Writing to Walrus...");
+            let blobs = large::write_campaign_blobs(
+                store.as_ref(),
+                &campaign.data,
+                state.epochs,
+                state.deletable,
+            )
+            .await?;
+            state.blobs = Some(blobs.clone());
+            state.save(state_path)?;
+            blobs
+        }
+    };
+
+    // The transaction of an earlier run may have landed after all
+    if let Some(digest) = state.tx_digest {
+        let Some(effects) = wait_for_effects(client, digest).await? else {
+            return Err(anyhow::anyhow!(
+                "Transaction {} from an earlier run isn't known to the network yet. Check it on an explorer, then retry or pass --force-new-tx to `large resume {}`",
+                digest,
+                state_path.display()
+            ));
+        };
+        if let ExecutionStatus::Success = effects.status() {
+            println!("Transaction {} already executed", digest);
+            return record_created(state, state_path, &effects);
+        }
+        println!("Transaction {} failed, retrying", digest);
+    }
+
+    println!("Creating transaction...");
+    let tx = txns::create_drop_tx(
+        client,
+        net,
+        &wallet,
+        &blobs.allocations.object_address,
+        &blobs.tree_object(),
+        campaign.total,
+        campaign.tree.leaf_count(),
+        &state.coin_type,
+        &campaign.tree.root(),
+        state.tree_version,
+    )
+    .await?;
+
+    println!("Signing transaction...");
+    let sig = state.sign_create_tx(state_path, signer, &tx).await?;
+    println!("Submitting transaction...");
+    let res = client
+        .execute_tx(vec![sig], &tx)
+        .await?
+        .ok_or(anyhow::anyhow!("missing tx"))?;
+
+    println!("TX status: {:?}", res.status());
+    println!("TX digest: {}", tx.digest());
+    if !matches!(res.status(), ExecutionStatus::Success) {
+        return Err(anyhow::anyhow!(
+            "Transaction failed, retry with `large resume {}`",
+            state_path.display()
+        ));
+    }
+    record_created(state, state_path, &res)
 }
 
-const EPOCHS: u32 = 4;
+/// How long to wait for an earlier transaction to be indexed before giving
+/// up on it.
+const TX_WAIT: Duration = Duration::from_secs(30);
+const TX_POLL: Duration = Duration::from_secs(3);
+
+/// Effects of a submitted transaction, polling while a lagging node catches
+/// up. `None` if it's still unknown after `TX_WAIT`.
+async fn wait_for_effects(
+    client: &sui_graphql_client::Client,
+    digest: TransactionDigest,
+) -> anyhow::Result<Option<TransactionEffects>> {
+    let deadline = tokio::time::Instant::now() + TX_WAIT;
+    loop {
+        if let Some(effects) = client.transaction_effects(digest).await? {
+            return Ok(Some(effects));
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Ok(None);
+        }
+        println!("Waiting for transaction {}...", digest);
+        tokio::time::sleep(TX_POLL).await;
+    }
+}
+
+fn record_created(
+    state: &mut CreateState,
+    state_path: &Path,
+    effects: &TransactionEffects,
+) -> anyhow::Result<ObjectId> {
+    let drop_id = sui::find_created_shared_obj(effects)?;
+    state.drop_id = Some(drop_id);
+    state.save(state_path)?;
+    Ok(drop_id)
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let wallet_task = tokio::spawn(ffi::current_wallet());
+    let cli = Cli::parse();
+    if let Commands::Config { action } = &cli.command {
+        run_config(&cli, action.as_ref().unwrap_or(&ConfigAction::Show))?;
+        return Ok(());
+    }
 
-    let client = sui_graphql_client::Client::new_testnet();
+    let profile = config::load_profile(&profile_name(&cli))?;
+    let net = network_config(&cli, &profile);
+    let client = net.client()?;
+    match &cli.command {
+        Commands::CreateDrop {
+            path,
+            coin_type,
+            epochs,
+            deletable,
+            permanent,
+            compress,
+            no_tree_blob,
+            tree_version,
+            yes,
+            build,
+            state: state_path,
+        } => {
+            let signer = signer(&cli, &profile)?;
+            if let StoreKind::WalrusCli = cli.store {
+                ffi::walrus_check().await?;
+            }
+            let state_path = state_path
+                .clone()
+                .unwrap_or_else(|| CreateState::default_path(path));
+            if state_path.exists() {
+                return Err(format!(
+                    "{} already exists, continue with `large resume {}` or pass another --state",
+                    state_path.display(),
+                    state_path.display()
+                )
+                .into());
+            }
+            // Only prompt for missing values when someone is there to answer
+            let interactive = std::io::stdin().is_terminal();
 
-    let cli = Cli::parse();
-    match cli.command {
-        Commands::CreateDrop { path } => {
-            ffi::env_check().await?;
-
-            let data = wallets::read_wallets_csv(&path)?;
-            let coin_txt = inquire::Text::new("What coin type do you want to airdrop?").prompt()?;
-            let coin_type = TypeTag::from_str(&coin_txt)?;
-
-            let (total, wallets) = wallets::clean_addresses(data)?;
-            println!("Wallet count: {}", wallets.len());
-            println!("Airdrop token total: {}", total);
-            println!("Building merkle tree...");
-            let merk = {
-                let roots: Vec<_> = wallets
-                    .iter()
-                    .map(|(addr, allo)| wallets::hash_allo(addr, *allo))
-                    .collect();
-                MerkleTree::new(&roots)?
-            };
-            let merkle_bts = bcs::to_bytes(&merk)?;
-
-            let top_root = merk.get_root();
-            let address_bts = wallets::write_wallets_to_bytes(&wallets)?;
+            let coin_type = match coin_type {
+                Some(coin_type) => coin_type.clone(),
+                None if interactive => {
+                    let coin_txt =
+                        inquire::Text::new("What coin type do you want to airdrop?").prompt()?;
+                    TypeTag::from_str(&coin_txt)?
+                }
+                None => return Err("--coin-type is required when not running interactively".into()),
+            };
+            let epochs = match epochs {
+                Some(epochs) => *epochs,
+                None if interactive => inquire::CustomType::<u32>::new(
+                    "How many epochs should the blobs be stored for?",
+                )
+                .with_default(profile.epochs())
+                .prompt()?,
+                None => profile.epochs(),
+            };
+            let deletable = match (deletable, permanent) {
+                (_, true) => false,
+                (true, _) => true,
+                _ if interactive => inquire::Confirm::new("Should the blobs be deletable?")
+                    .with_default(true)
+                    .prompt()?,
+                _ => true,
+            };
+            let coin = sui::fetch_coin_info(&client, &coin_type).await?;
 
-            println!("Writing to Walrus...");
-            let blobs = ffi::write_blobs(vec![&merkle_bts, &address_bts], EPOCHS).await?;
-            let merkle_addr = blobs.first().ok_or("missing merkle blob")?.object_address;
-            let list_addr = blobs.get(1).ok_or("missing addresses blob")?.object_address;
+            let mut state = CreateState::new(
+                std::fs::canonicalize(path)?,
+                net.network,
+                net.package_id()?,
+                coin_type,
+                epochs,
+                deletable,
+                *compress,
+            );
+            state.skip_tree = *no_tree_blob;
+            state.tree_version = *tree_version;
+            let campaign = prepare_campaign(&mut state, build)?;
+            let [tree_size, list_size] = campaign.data.sizes();
+            let tree_desc = if state.skip_tree {
+                "no merkle tree".to_string()
+            } else {
+                format!(
+                    "{} {} merkle tree",
+                    format_size(tree_size),
+                    state.tree_version
+                )
+            };
+
+            println!();
+            println!("Coin type: {}", state.coin_type);
+            println!("Wallet count: {}", campaign.tree.leaf_count());
+            println!(
+                "Airdrop total: {} ${}",
+                wallets::format_amount(campaign.total, coin.decimals),
+                coin.symbol
+            );
+            println!(
+                "Blobs: {}, {} allocations, {} for {} epochs",
+                tree_desc,
+                format_size(list_size),
+                if deletable { "deletable" } else { "permanent" },
+                epochs
+            );
+            if state.compress {
+                // The tree blob stays uncompressed so it can be range read
+                let raw = campaign.raw_sizes[1];
+                println!(
+                    "Compressed: {} allocations, down from {} ({:.0}% smaller), the merkle tree is stored uncompressed",
+                    format_size(list_size),
+                    format_size(raw),
+                    100.0 * (1.0 - list_size as f64 / raw as f64)
+                );
+            }
+            if !matches!(cli.store, StoreKind::Dir) {
+                let price = walrus::StoragePrice::default();
+                // A tree that isn't stored costs nothing
+                let uploaded = |sizes: [u64; 2]| -> Vec<u64> {
+                    sizes.into_iter().filter(|size| *size > 0).collect()
+                };
+                let cost = price.estimate(&uploaded(campaign.data.sizes()), epochs);
+                println!(
+                    "Estimated Walrus cost: ~{} WAL",
+                    wallets::format_amount(cost, 9)
+                );
+                if state.compress {
+                    let saved = price
+                        .estimate(&uploaded(campaign.raw_sizes), epochs)
+                        .saturating_sub(cost);
+                    println!(
+                        "Estimated saving from compression: ~{} WAL",
+                        wallets::format_amount(saved, 9)
+                    );
+                }
+            }
+            println!(
+                "Gas: up to {} SUI (gas price {} MIST)",
+                wallets::format_amount(net.gas.budget, 9),
+                net.gas.price
+            );
+            println!();
 
-            println!("Creating transaction...");
-            let wallet = wallet_task.await??;
-            let tx = txns::create_drop_tx(
+            if !confirm("Upload the blobs and create the campaign?", *yes)? {
+                println!("Aborted");
+                return Ok(());
+            }
+
+            state.save(&state_path)?;
+            println!("Progress is saved to: {}", state_path.display());
+            let new_campaign_id = finish_campaign(
+                &cli,
+                &net,
                 &client,
-                &wallet,
-                &list_addr,
-                &merkle_addr,
-                total,
-                merk.leaf_count,
-                &coin_type,
-                &top_root,
+                signer.as_ref(),
+                &mut state,
+                &state_path,
+                &campaign,
             )
             .await?;
+            println!("New campaign object ID: {new_campaign_id}");
+        }
+        Commands::Resume {
+            state: state_path,
+            build,
+            force_new_tx,
+        } => {
+            let mut state = CreateState::load(state_path)?;
+            println!("Last completed stage: {}", state.stage());
+            if let Some(drop_id) = state.drop_id {
+                println!("Campaign already created: {}", drop_id);
+                return Ok(());
+            }
+            if state.network != net.network {
+                return Err(format!(
+                    "This campaign was started on {}, pass --network {}",
+                    state.network, state.network
+                )
+                .into());
+            }
+            let net = NetworkConfig {
+                package_id: Some(state.package_id),
+                ..net.clone()
+            };
+            if *force_new_tx && let Some(digest) = state.tx_digest.take() {
+                println!("Ignoring transaction {} from the earlier run", digest);
+            }
 
-            println!("Signing transaction...");
-            let sig = ffi::sign_tx(&wallet, &tx).await?;
-            println!("Submitting transaction...");
-            let res = client
-                .execute_tx(vec![sig], &tx)
-                .await?
-                .ok_or("missing tx")?;
-
-            println!("TX status: {:?}", res.status());
-            println!("TX digest: {}", tx.digest());
-            let new_campaign_id = sui::find_created_shared_obj(&res)?;
+            let signer = signer(&cli, &profile)?;
+            if let StoreKind::WalrusCli = cli.store {
+                ffi::walrus_check().await?;
+            }
+            let campaign = prepare_campaign(&mut state, build)?;
+            let new_campaign_id = finish_campaign(
+                &cli,
+                &net,
+                &client,
+                signer.as_ref(),
+                &mut state,
+                state_path,
+                &campaign,
+            )
+            .await?;
             println!("New campaign object ID: {new_campaign_id}");
         }
         Commands::CurrentWallet => {
-            ffi::sui_check().await?;
-            let wallet = wallet_task.await??;
+            let wallet = signer(&cli, &profile)?.address().await?;
             println!("Active wallet: {:?}", wallet);
         }
         Commands::Claim { drop_id } => {
-            ffi::sui_check().await?;
-
-            let wallet = wallet_task.await??;
+            let signer = signer(&cli, &profile)?;
+            let wallet = signer.address().await?;
             println!("Active wallet: {}", wallet);
 
-            let drop_obj = drop_id.unwrap_or(drop_object());
+            let drop_obj = match drop_id {
+                Some(drop_id) => *drop_id,
+                None => net.default_drop()?,
+            };
             println!("Claiming from drop: {}", drop_obj);
+            let store = read_store(&cli, &net, &profile)?;
             let tt = sui::fetch_type_param(&client, &drop_obj).await?;
-            let data: txns::Drop = sui::fetch_bcs(&client, &drop_obj).await?;
+            let data = txns::fetch_drop(&client, &drop_obj).await?;
+
+            if let Some(tx_digest) = txns::find_claim(&client, &data, &wallet).await? {
+                return Err(format!("{} has already claimed, in tx {}", wallet, tx_digest).into());
+            }
 
             let start_time = std::time::Instant::now();
             let mut sp = Spinner::new(Spinners::Aesthetic, "Reading blobs...".into());
-            let (merkle_tree, addresses) = futures::future::try_join(
-                large::fetch_merkle_tree(&client, &data.merkle_tree),
-                large::fetch_allocations(&client, &data.allocations),
-            )
-            .await?;
+            let claim = large::fetch_claim_proof(&client, store.as_ref(), &data, &wallet).await?;
             let total_elapsed = start_time.elapsed().as_millis();
             sp.stop_with_message(format!("Done in {:.2}s", total_elapsed as f64 / 1000.0));
 
-            let allo = addresses
-                .get_allocation(&wallet)
-                .ok_or("no allocation found")?;
-            let leaf = wallets::hash_allo(&wallet, allo);
-
-            let (leaf_index, proof) = merkle_tree.get_proof(&leaf);
-
-            assert!(merkle_tree.verify_proof(&leaf, &proof), "Invalid proof");
-
-            let tx =
-                txns::create_claim_tx(&client, &wallet, &proof, leaf_index, &drop_obj, &tt, allo)
-                    .await?;
+            let tx = txns::create_claim_tx(
+                &client,
+                &net,
+                &wallet,
+                &claim.proof,
+                claim.leaf_index,
+                &drop_obj,
+                &tt,
+                claim.allocation,
+            )
+            .await?;
 
-            let sig = ffi::sign_tx(&wallet, &tx).await?;
+            let sig = signer.sign_tx(&tx).await?;
             let res = client
                 .execute_tx(vec![sig], &tx)
                 .await?
@@ -166,39 +1017,561 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("TX status: {:?}", res.status());
             println!("TX digest: {}", tx.digest());
         }
+        Commands::ClaimMany {
+            input,
+            drop_id,
+            yes,
+        } => {
+            let signer = signer(&cli, &profile)?;
+            let sender = signer.address().await?;
+            let wallet_list = wallets::read_address_list(input)?;
+            let drop_id = match drop_id {
+                Some(drop_id) => *drop_id,
+                None => net.default_drop()?,
+            };
+            txns::check_claim_many(&client, &net).await?;
+            let store = read_store(&cli, &net, &profile)?;
+            let tt = sui::fetch_type_param(&client, &drop_id).await?;
+            let drop = txns::fetch_drop(&client, &drop_id).await?;
+            let coin = sui::fetch_coin_info(&client, &tt).await?;
+
+            println!("Reading blobs...");
+            let addresses =
+                large::fetch_allocations(&client, store.as_ref(), &drop.allocations).await?;
+            let merkle_tree =
+                large::open_campaign_tree(&client, store.as_ref(), &drop, &addresses).await?;
+
+            // A claimed wallet would abort the whole transaction
+            println!("Checking {} wallets...", wallet_list.len());
+            let statuses: Vec<_> = futures::stream::iter(wallet_list.iter().map(|wallet| {
+                let (client, drop) = (&client, &drop);
+                let allocation = addresses.get_allocation(wallet);
+                async move {
+                    let claim_tx = match allocation {
+                        Some(_) => txns::find_claim(client, drop, wallet).await?,
+                        None => None,
+                    };
+                    anyhow::Ok((*wallet, txns::ClaimStatus::new(claim_tx, allocation)))
+                }
+            }))
+            .buffered(8)
+            .try_collect()
+            .await?;
+            let mut unclaimed: Vec<Address> = statuses
+                .iter()
+                .filter(|(_, status)| *status == txns::ClaimStatus::Unclaimed)
+                .map(|(wallet, _)| *wallet)
+                .collect();
+            // Neighbouring leaves share proof nodes
+            unclaimed.sort();
+            unclaimed.dedup();
+            for (wallet, status) in &statuses {
+                if *status != txns::ClaimStatus::Unclaimed {
+                    println!("Skipping {}: {}", wallet, status);
+                }
+            }
+            if unclaimed.is_empty() {
+                println!("No wallets left to claim for");
+                return Ok(());
+            }
+
+            let total: u64 = unclaimed
+                .iter()
+                .filter_map(|wallet| addresses.get_allocation(wallet))
+                .sum();
+            let batches = unclaimed.len().div_ceil(CLAIM_BATCH);
+            println!(
+                "Claiming {} ${} for {} wallets in {} transactions",
+                wallets::format_amount(total, coin.decimals),
+                coin.symbol,
+                unclaimed.len(),
+                batches
+            );
+            if !confirm("Submit the claims?", *yes)? {
+                println!("Aborted");
+                return Ok(());
+            }
+
+            for (i, batch) in unclaimed.chunks(CLAIM_BATCH).enumerate() {
+                let claim = merkle_tree
+                    .multi_claim(store.as_ref(), &drop, &addresses, batch)
+                    .await?;
+                let tx = txns::create_claim_many_tx(&client, &net, &sender, &claim, &drop_id, &tt)
+                    .await?;
+                let sig = signer.sign_tx(&tx).await?;
+                let res = client
+                    .execute_tx(vec![sig], &tx)
+                    .await?
+                    .ok_or("missing tx")?;
+                println!(
+                    "[{}/{}] {} wallets, {} proof nodes, TX {} {:?}",
+                    i + 1,
+                    batches,
+                    batch.len(),
+                    claim.proof.nodes.len(),
+                    tx.digest(),
+                    res.status()
+                );
+                if !matches!(res.status(), ExecutionStatus::Success) {
+                    return Err(
+                        "Claim transaction failed, rerun to retry the unclaimed wallets".into(),
+                    );
+                }
+            }
+        }
+        Commands::Proof {
+            wallet,
+            drop_id,
+            format,
+        } => {
+            let drop_id = match drop_id {
+                Some(drop_id) => *drop_id,
+                None => net.default_drop()?,
+            };
+            let store = read_store(&cli, &net, &profile)?;
+            let tt = sui::fetch_type_param(&client, &drop_id).await?;
+            let data = txns::fetch_drop(&client, &drop_id).await?;
+
+            // Progress goes to stderr so stdout can be piped
+            eprintln!("Reading blobs...");
+            let claim = large::fetch_claim_proof(&client, store.as_ref(), &data, wallet).await?;
+            let [proof_arg, leaf_index_arg, allocation_arg] = claim.claim_args()?;
+            let target = format!("{}::drop::claim", net.call_package_id()?);
+            let hex = |bts: &[u8]| format!("0x{}", hex::encode(bts));
+
+            match format {
+                ProofFormat::Json => {
+                    let b64 = |bts: &[u8]| base64::engine::general_purpose::STANDARD.encode(bts);
+                    let output = ProofOutput {
+                        drop_id,
+                        target,
+                        type_arguments: vec![tt.to_string()],
+                        wallet: claim.wallet,
+                        allocation: claim.allocation,
+                        leaf_index: claim.leaf_index,
+                        root: hex(&claim.root),
+                        tree_version: claim.version,
+                        proof: claim.proof.iter().map(|h| hex(h)).collect(),
+                        claim_args: ClaimArgs {
+                            proof: b64(&proof_arg),
+                            leaf_index: b64(&leaf_index_arg),
+                            allocation: b64(&allocation_arg),
+                        },
+                    };
+                    println!("{}", serde_json::to_string_pretty(&output)?);
+                }
+                ProofFormat::Hex => {
+                    println!("drop_id: {}", drop_id);
+                    println!("target: {}", target);
+                    println!("type_argument: {}", tt);
+                    println!("wallet: {}", claim.wallet);
+                    println!("allocation: {}", claim.allocation);
+                    println!("leaf_index: {}", claim.leaf_index);
+                    println!("root: {}", hex(&claim.root));
+                    println!("tree_version: {}", u8::from(claim.version));
+                    for (i, hash) in claim.proof.iter().enumerate() {
+                        println!("proof[{}]: {}", i, hex(hash));
+                    }
+                    println!("claim_args.proof: {}", hex(&proof_arg));
+                    println!("claim_args.leaf_index: {}", hex(&leaf_index_arg));
+                    println!("claim_args.allocation: {}", hex(&allocation_arg));
+                }
+            }
+        }
         Commands::CheckClaim { wallet, drop_id } => {
-            let sender = wallet.unwrap_or(wallet_task.await??);
-            let drop_obj_id = drop_id.unwrap_or(drop_object());
+            let sender = match wallet {
+                Some(wallet) => *wallet,
+                None => signer(&cli, &profile)?.address().await?,
+            };
+            let drop_obj_id = match drop_id {
+                Some(drop_id) => *drop_id,
+                None => net.default_drop()?,
+            };
+            let store = read_store(&cli, &net, &profile)?;
             println!("Checking claim in drop ID: {}", drop_obj_id);
             println!("Wallet selected: {}", sender);
-            let drop_obj: txns::Drop = sui::fetch_bcs(&client, &drop_obj_id).await?;
+            let drop_obj = txns::fetch_drop(&client, &drop_obj_id).await?;
+            let claim_tx = txns::find_claim(&client, &drop_obj, &sender).await?;
 
             let tt = sui::fetch_type_param(&client, &drop_obj_id).await?;
 
-            let coin = client
-                .coin_metadata(&tt.to_string())
-                .await?
-                .ok_or("coin not found")?;
-            let decimals = coin.decimals.ok_or("unknown decimals")? as u32;
-            let shift = 10_f64.powf(decimals as f64);
+            let coin = sui::fetch_coin_info(&client, &tt).await?;
+            let shift = 10_f64.powf(coin.decimals as f64);
 
             let mut sp = Spinner::new(Spinners::Aesthetic, "Reading from Walrus...".into());
-            let addresses = large::fetch_allocations(&client, &drop_obj.allocations).await?;
+            let addresses =
+                large::fetch_allocations(&client, store.as_ref(), &drop_obj.allocations).await?;
             sp.stop_with_newline();
 
-            let allo = addresses.get_allocation(&sender).unwrap_or(0);
+            let allocation = addresses.get_allocation(&sender);
+            let allo = allocation.unwrap_or(0);
 
             println!("Allocation for wallet: {}", sender);
+            println!("{:.2} ${}", allo as f64 / shift, coin.symbol);
+            println!("Status: {}", txns::ClaimStatus::new(claim_tx, allocation));
+        }
+        Commands::CheckClaims {
+            input,
+            drop_id,
+            format,
+            output,
+        } => {
+            let wallet_list = wallets::read_address_list(input)?;
+            let drop_id = match drop_id {
+                Some(drop_id) => *drop_id,
+                None => net.default_drop()?,
+            };
+            let store = read_store(&cli, &net, &profile)?;
+            let tt = sui::fetch_type_param(&client, &drop_id).await?;
+            let drop = txns::fetch_drop(&client, &drop_id).await?;
+            let coin = sui::fetch_coin_info(&client, &tt).await?;
+
+            // Progress goes to stderr so stdout can be piped
+            eprintln!("Reading blobs...");
+            // Leaves are in allocations order, the tree isn't needed
+            let addresses =
+                large::fetch_allocations(&client, store.as_ref(), &drop.allocations).await?;
+
+            eprintln!("Checking {} wallets...", wallet_list.len());
+            let rows: Vec<ClaimCheck> = futures::stream::iter(wallet_list.iter().map(|wallet| {
+                let (client, drop, coin) = (&client, &drop, &coin);
+                let allocation = addresses.get_allocation(wallet);
+                let leaf_index = addresses.get_position(wallet).map(|i| i as u64);
+                async move {
+                    // Only wallets in the list can have claimed
+                    let claim_tx = match allocation {
+                        Some(_) => txns::find_claim(client, drop, wallet).await?,
+                        None => None,
+                    };
+                    let status = txns::ClaimStatus::new(claim_tx, allocation);
+                    let allocation = allocation.unwrap_or(0);
+                    anyhow::Ok(ClaimCheck {
+                        wallet: *wallet,
+                        allocation,
+                        amount: wallets::format_amount(allocation, coin.decimals),
+                        status: status.label(),
+                        claim_tx: status.tx_digest(),
+                        leaf_index,
+                    })
+                }
+            }))
+            .buffered(8)
+            .try_collect()
+            .await?;
+
+            let writer: Box<dyn std::io::Write> = match output {
+                Some(path) => Box::new(std::fs::File::create(path)?),
+                None => Box::new(std::io::stdout()),
+            };
+            match format {
+                ReportFormat::Csv => {
+                    let mut wtr = csv::Writer::from_writer(writer);
+                    for row in &rows {
+                        wtr.serialize(row)?;
+                    }
+                    wtr.flush()?;
+                }
+                ReportFormat::Json => {
+                    let mut writer = writer;
+                    serde_json::to_writer_pretty(&mut writer, &rows)?;
+                    writeln!(writer)?;
+                }
+            }
+
+            let eligible = rows.iter().filter(|row| row.allocation > 0).count();
+            let claimed = rows.iter().filter(|row| row.claim_tx.is_some()).count();
+            eprintln!(
+                "{} of {} wallets eligible, {} claimed",
+                eligible,
+                rows.len(),
+                claimed
+            );
+        }
+        Commands::DeleteDrop { drop_id, yes } => {
+            let signer = signer(&cli, &profile)?;
+            let wallet = signer.address().await?;
+            println!("Active wallet: {}", wallet);
+
+            let tt = sui::fetch_type_param(&client, drop_id).await?;
+            let drop = txns::fetch_drop(&client, drop_id).await?;
+            let cap_id = txns::get_delete_cap(&client, &net, &wallet, drop_id)
+                .await
+                .context("Failed to look up DeleteCaps")?
+                .ok_or(format!(
+                    "{} doesn't own the DeleteCap for {}",
+                    wallet, drop_id
+                ))?;
+            let coin = sui::fetch_coin_info(&client, &tt).await?;
+
+            println!("Campaign: {}", drop_id);
+            println!("Coin type: {}", tt);
             println!(
-                "{:.2} ${}",
-                allo as f64 / shift,
-                coin.symbol.unwrap_or("TOKEN".to_string())
+                "Claimed: {} of {} wallets",
+                drop.registry.size, drop.wallet_count
             );
+            println!(
+                "Vault balance: {} of {} ${}",
+                wallets::format_amount(drop.vault, coin.decimals),
+                wallets::format_amount(drop.airdrop_total, coin.decimals),
+                coin.symbol
+            );
+            println!();
+
+            let question = "Close the campaign? Wallets that haven't claimed yet won't be able to";
+            if !confirm(question, *yes)? {
+                println!("Aborted");
+                return Ok(());
+            }
+
+            let tx = txns::delete_drop_tx(&client, &net, &wallet, &tt, drop_id, &cap_id).await?;
+            let sig = signer.sign_tx(&tx).await?;
+            let res = client
+                .execute_tx(vec![sig], &tx)
+                .await?
+                .ok_or("missing tx")?;
+
+            println!("TX status: {:?}", res.status());
+            println!("TX digest: {}", tx.digest());
+            if let ExecutionStatus::Success = res.status() {
+                let coin_id = sui::find_created_owned_obj(&res, &wallet)?;
+                println!(
+                    "Returned coin: {} ({} ${})",
+                    coin_id,
+                    wallets::format_amount(drop.vault, coin.decimals),
+                    coin.symbol
+                );
+            }
+        }
+        Commands::VerifyDrop { drop_id } => {
+            let drop_id = match drop_id {
+                Some(drop_id) => *drop_id,
+                None => net.default_drop()?,
+            };
+            let store = read_store(&cli, &net, &profile)?;
+            let drop = txns::fetch_drop(&client, &drop_id).await?;
+            println!("Verifying campaign: {}", drop_id);
+
+            let mut sp = Spinner::new(Spinners::Aesthetic, "Reading blobs...".into());
+            // Without a tree blob the audit's rebuilt tree is the only one
+            let (merkle_tree, addresses) = match drop.tree_blob() {
+                Some(object) => {
+                    let (tree, addresses) = futures::future::try_join(
+                        large::fetch_merkle_tree(&client, store.as_ref(), &object),
+                        large::fetch_allocations(&client, store.as_ref(), &drop.allocations),
+                    )
+                    .await?;
+                    (Some(tree), addresses)
+                }
+                None => (
+                    None,
+                    large::fetch_allocations(&client, store.as_ref(), &drop.allocations).await?,
+                ),
+            };
+            sp.stop_with_newline();
+
+            let report = audit::audit_drop(&drop, merkle_tree.as_ref(), &addresses);
+            for check in &report.checks {
+                let mark = if check.passed { "✅" } else { "❌" };
+                println!("{} {}: {}", mark, check.name, check.detail);
+            }
+            if !report.passed() {
+                return Err("FAIL: the campaign data doesn't match the onchain campaign".into());
+            }
+            println!("PASS");
+        }
+        Commands::Status {
+            drop_id,
+            warn_epochs,
+        } => {
+            let drop_id = match drop_id {
+                Some(drop_id) => *drop_id,
+                None => net.default_drop()?,
+            };
+            let tt = sui::fetch_type_param(&client, &drop_id).await?;
+            let drop = txns::fetch_drop(&client, &drop_id).await?;
+            let coin = sui::fetch_coin_info(&client, &tt).await?;
+            let amount = |value: u64| wallets::format_amount(value, coin.decimals);
+            let percent = |part: u64, whole: u64| match whole {
+                0 => 0.0,
+                _ => part as f64 * 100.0 / whole as f64,
+            };
+
+            let claimed_amount = drop.airdrop_total.saturating_sub(drop.vault);
+            println!("Campaign: {}", drop_id);
+            println!("Coin type: {}", tt);
+            println!(
+                "Claimed wallets: {} of {} ({:.2}%)",
+                drop.registry.size,
+                drop.wallet_count,
+                percent(drop.registry.size, drop.wallet_count as u64)
+            );
+            println!(
+                "Claimed amount: {} of {} ${} ({:.2}%)",
+                amount(claimed_amount),
+                amount(drop.airdrop_total),
+                coin.symbol,
+                percent(claimed_amount, drop.airdrop_total)
+            );
+            println!(
+                "Remaining in vault: {} ${}",
+                amount(drop.vault),
+                coin.symbol
+            );
+            println!("Tree format: {}", drop.tree_version);
+
+            let store = read_store(&cli, &net, &profile)?;
+            let blobs: Vec<_> = drop
+                .tree_blob()
+                .map(|object| ("Merkle tree", object))
+                .into_iter()
+                .chain([("Allocations", drop.allocations)])
+                .collect();
+            if drop.tree_blob().is_none() {
+                println!("Merkle tree: not stored, rebuilt from the allocations");
+            }
+            if blobs
+                .iter()
+                .any(|(_, object)| store.blob_id_for_object(object).is_some())
+            {
+                println!("Blobs are kept in a local store and don't expire");
+                return Ok(());
+            }
+
+            let epoch = match net.walrus_system {
+                Some(system) => Some(
+                    sui::fetch_walrus_epoch(&client, &system)
+                        .await
+                        .context("Failed to read the current Walrus epoch")?,
+                ),
+                None => None,
+            };
+            match epoch {
+                Some(epoch) => println!("Walrus epoch: {}", epoch),
+                None => println!("Walrus epoch: unknown"),
+            }
+
+            let mut warnings = Vec::new();
+            for (name, object) in blobs {
+                let blob: walrus::BlobObject =
+                    sui::fetch_bcs(&client, &ObjectId::from(object)).await?;
+                let end = blob.storage.end_epoch;
+                let Some(epoch) = epoch else {
+                    println!(
+                        "{} blob {}: stored until epoch {}",
+                        name,
+                        blob.blob_id(),
+                        end
+                    );
+                    continue;
+                };
+                let left = end.saturating_sub(epoch);
+                let days = net
+                    .network
+                    .walrus_epoch_days()
+                    .map(|days| format!(", ~{} days", left * days))
+                    .unwrap_or_default();
+                println!(
+                    "{} blob {}: stored until epoch {} ({} epochs left{})",
+                    name,
+                    blob.blob_id(),
+                    end,
+                    left,
+                    days
+                );
+                if left == 0 {
+                    warnings.push(format!(
+                        "{} blob has expired, claims can no longer read it",
+                        name
+                    ));
+                } else if left <= *warn_epochs {
+                    warnings.push(format!(
+                        "{} blob expires in {} epochs, extend it with `walrus extend --blob-obj-id {} --epochs-extended <N>`",
+                        name, left, object
+                    ));
+                }
+            }
+            for warning in warnings {
+                println!("⚠️  {}", warning);
+            }
+        }
+        Commands::ListDrops { owner, json } => {
+            let owner = match owner {
+                Some(owner) => *owner,
+                None => signer(&cli, &profile)?.address().await?,
+            };
+            let caps = txns::list_delete_caps(&client, &net, &owner).await?;
+
+            let drops: Vec<_> = futures::stream::iter(caps.iter().map(|cap| {
+                let drop_id = ObjectId::from(cap.object_id);
+                let client = &client;
+                async move {
+                    let (tt, drop) = futures::future::try_join(
+                        sui::fetch_type_param(client, &drop_id),
+                        txns::fetch_drop(client, &drop_id),
+                    )
+                    .await?;
+                    anyhow::Ok((drop_id, tt, drop))
+                }
+            }))
+            .buffered(8)
+            .try_collect()
+            .await?;
+
+            let mut coins = HashMap::new();
+            let mut summaries = Vec::with_capacity(drops.len());
+            for (drop_id, tt, drop) in drops {
+                let coin_type = tt.to_string();
+                if !coins.contains_key(&coin_type) {
+                    coins.insert(coin_type.clone(), sui::fetch_coin_info(&client, &tt).await?);
+                }
+                let coin = &coins[&coin_type];
+                summaries.push(DropSummary {
+                    drop_id,
+                    symbol: coin.symbol.clone(),
+                    decimals: coin.decimals,
+                    coin_type,
+                    wallet_count: drop.wallet_count,
+                    claimed: drop.registry.size,
+                    airdrop_total: drop.airdrop_total,
+                    vault: drop.vault,
+                });
+            }
+
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&summaries)?);
+            } else if summaries.is_empty() {
+                println!("No campaigns found for: {}", owner);
+            } else {
+                println!(
+                    "{:<66}  {:<10}  {:>10}  {:>10}  {:>18}  {:>18}",
+                    "DROP ID", "COIN", "WALLETS", "CLAIMED", "TOTAL", "VAULT"
+                );
+                for drop in &summaries {
+                    println!(
+                        "{:<66}  {:<10}  {:>10}  {:>10}  {:>18}  {:>18}",
+                        drop.drop_id.to_string(),
+                        drop.symbol,
+                        drop.wallet_count,
+                        drop.claimed,
+                        wallets::format_amount(drop.airdrop_total, drop.decimals),
+                        wallets::format_amount(drop.vault, drop.decimals)
+                    );
+                }
+            }
         }
+        Commands::Config { .. } => unreachable!(),
         Commands::CheckEnv => {
-            let (sui_version, walrus_version) = ffi::env_check().await?;
-            println!("✅ Sui CLI: {}", sui_version);
-            println!("✅ Walrus CLI: {}", walrus_version);
+            let wallet = signer(&cli, &profile)?.address().await?;
+            println!("✅ Signer wallet: {}", wallet);
+            println!("✅ Network: {} ({})", net.network, net.graphql_url);
+            match ffi::sui_check().await {
+                Ok(sui_version) => println!("✅ Sui CLI: {}", sui_version),
+                Err(_) => println!("➖ Sui CLI: not installed (optional)"),
+            }
+            match ffi::walrus_check().await {
+                Ok(walrus_version) => println!("✅ Walrus CLI: {}", walrus_version),
+                Err(_) => println!("➖ Walrus CLI: not installed (optional)"),
+            }
         }
     }
 
@@ -1,19 +1,128 @@
 use blake2::{Blake2b, Digest};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 pub type Hash = [u8; 32];
 
 pub type Proof = Vec<Hash>;
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// Prefix of leaf hashes in v2 trees, mirrored in `sources/drop.move`.
+pub const LEAF_TAG: u8 = 0;
+/// Prefix of internal node hashes in v2 trees.
+pub const NODE_TAG: u8 = 1;
+
+/// How leaves and nodes are hashed. v1 hashes are plain Blake2b-256, so an
+/// internal node could be passed off as a leaf. v2 prefixes leaves with
+/// `LEAF_TAG` and nodes with `NODE_TAG`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub enum TreeVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl TreeVersion {
+    pub fn hash_node(self, left: &Hash, right: &Hash) -> Hash {
+        match self {
+            TreeVersion::V1 => hash_pair(left, right),
+            TreeVersion::V2 => {
+                let mut hasher = Blake2b::new();
+                hasher.update([NODE_TAG]);
+                hasher.update(left);
+                hasher.update(right);
+                hasher.finalize().into()
+            }
+        }
+    }
+}
+
+impl TryFrom<u8> for TreeVersion {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> anyhow::Result<Self> {
+        match value {
+            1 => Ok(TreeVersion::V1),
+            2 => Ok(TreeVersion::V2),
+            _ => Err(anyhow::anyhow!("Unsupported tree version: {}", value)),
+        }
+    }
+}
+
+impl From<TreeVersion> for u8 {
+    fn from(version: TreeVersion) -> u8 {
+        match version {
+            TreeVersion::V1 => 1,
+            TreeVersion::V2 => 2,
+        }
+    }
+}
+
+impl std::fmt::Display for TreeVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "v{}", u8::from(*self))
+    }
+}
+
+impl std::str::FromStr for TreeVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        value.trim_start_matches('v').parse::<u8>()?.try_into()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(from = "StoredTree")]
 pub struct MerkleTree {
     pub root: Hash,
     pub leaf_count: u32,
     levels: Vec<Vec<Hash>>,
+    /// Not part of the BCS blob, which only ever held v1 trees.
+    #[serde(skip)]
+    pub version: TreeVersion,
+    /// Leaf hash to leaf index. Not part of the blob, it's rebuilt on load.
+    #[serde(skip)]
+    index: HashMap<Hash, u64>,
+}
+
+/// The serialized layout of `MerkleTree`.
+#[derive(Deserialize)]
+struct StoredTree {
+    root: Hash,
+    leaf_count: u32,
+    levels: Vec<Vec<Hash>>,
+}
+
+impl From<StoredTree> for MerkleTree {
+    fn from(tree: StoredTree) -> Self {
+        let index = leaf_index(tree.levels.first().map(Vec::as_slice).unwrap_or_default());
+        MerkleTree {
+            root: tree.root,
+            leaf_count: tree.leaf_count,
+            levels: tree.levels,
+            version: TreeVersion::V1,
+            index,
+        }
+    }
+}
+
+/// Duplicate leaves resolve to the first occurrence.
+fn leaf_index(leaves: &[Hash]) -> HashMap<Hash, u64> {
+    let mut index = HashMap::with_capacity(leaves.len());
+    for (i, leaf) in leaves.iter().enumerate() {
+        index.entry(*leaf).or_insert(i as u64);
+    }
+    index
 }
 
 impl MerkleTree {
+    /// A v1 tree.
     pub fn new(leaves: &[Hash]) -> anyhow::Result<Self> {
+        Self::with_version(leaves, TreeVersion::V1)
+    }
+
+    pub fn with_version(leaves: &[Hash], version: TreeVersion) -> anyhow::Result<Self> {
         if leaves.len() < 2 {
             return Err(anyhow::anyhow!("insufficient leaves"));
         }
@@ -29,7 +138,7 @@ impl MerkleTree {
                 .map(|pair| {
                     let left = pair[0];
                     let right = pair.get(1).copied().unwrap_or(left);
-                    hash_pair(&left, &right)
+                    version.hash_node(&left, &right)
                 })
                 .collect();
             levels.push(current_layer.clone());
@@ -37,22 +146,79 @@ impl MerkleTree {
 
         Ok(MerkleTree {
             root: current_layer[0], // Last layer has single root node
+            index: leaf_index(leaves),
             levels,
             leaf_count,
+            version,
         })
     }
 
+    /// A tree from levels read back from storage, leaves first.
+    pub(crate) fn from_levels(
+        levels: Vec<Vec<Hash>>,
+        version: TreeVersion,
+    ) -> anyhow::Result<Self> {
+        let leaves = levels.first().ok_or(anyhow::anyhow!("empty tree"))?;
+        if leaves.len() < 2 {
+            return Err(anyhow::anyhow!("insufficient leaves"));
+        }
+        for pair in levels.windows(2) {
+            if pair[1].len() != pair[0].len().div_ceil(2) {
+                return Err(anyhow::anyhow!("malformed tree levels"));
+            }
+        }
+        let root = match levels.last().map(Vec::as_slice) {
+            Some([root]) => *root,
+            _ => return Err(anyhow::anyhow!("malformed tree levels")),
+        };
+        Ok(MerkleTree {
+            root,
+            leaf_count: u32::try_from(leaves.len())?,
+            index: leaf_index(leaves),
+            levels,
+            version,
+        })
+    }
+
+    pub(crate) fn levels(&self) -> &[Vec<Hash>] {
+        &self.levels
+    }
+
     pub fn get_root(&self) -> Hash {
         self.root
     }
 
     pub fn get_proof(&self, leaf: &Hash) -> (u64, Proof) {
         let leaf_index = self.get_leaf_index(leaf).expect("leaf not found");
+        (leaf_index, self.proof_at(leaf_index as usize))
+    }
+
+    /// The leaf at `leaf_index` and its proof.
+    pub fn leaf_proof(&self, leaf_index: u64) -> Option<LeafProof> {
+        let leaf = *self
+            .levels
+            .first()?
+            .get(usize::try_from(leaf_index).ok()?)?;
+        Some(LeafProof {
+            version: self.version,
+            root: self.root,
+            leaf_count: self.leaf_count,
+            leaf_index,
+            leaf,
+            proof: self.proof_at(leaf_index as usize),
+        })
+    }
+
+    fn proof_at(&self, leaf_index: usize) -> Proof {
         let mut proof = Vec::new();
-        let mut index = leaf_index as usize;
+        let mut index = leaf_index;
 
         for current_level in self.levels.iter().take(self.levels.len() - 1) {
-            let sibling_index = if index % 2 == 0 { index + 1 } else { index - 1 };
+            let sibling_index = if index.is_multiple_of(2) {
+                index + 1
+            } else {
+                index - 1
+            };
             let sibling = if sibling_index < current_level.len() {
                 current_level[sibling_index]
             } else {
@@ -62,23 +228,154 @@ impl MerkleTree {
             index /= 2;
         }
 
-        (leaf_index, proof)
+        proof
+    }
+
+    /// One proof for several leaves, in any order. `None` if there are no
+    /// leaves or one is out of range.
+    pub fn multi_proof(&self, leaf_indices: &[u64]) -> Option<MultiProof> {
+        let mut leaf_indices = leaf_indices.to_vec();
+        leaf_indices.sort_unstable();
+        leaf_indices.dedup();
+        if *leaf_indices.last()? >= self.leaf_count as u64 {
+            return None;
+        }
+
+        let mut nodes = Vec::new();
+        let mut known: Vec<usize> = leaf_indices.iter().map(|i| *i as usize).collect();
+        for level in self.levels.iter().take(self.levels.len() - 1) {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let index = known[i];
+                if !index.is_multiple_of(2) {
+                    nodes.push(level[index - 1]);
+                } else if known.get(i + 1) == Some(&(index + 1)) {
+                    // Both children are known, so is the parent
+                    i += 1;
+                } else if index + 1 < level.len() {
+                    nodes.push(level[index + 1]);
+                }
+                // Otherwise it's the last node of an odd level, paired with itself
+                parents.push(index / 2);
+                i += 1;
+            }
+            known = parents;
+        }
+
+        Some(MultiProof {
+            leaf_indices,
+            nodes,
+        })
     }
 
     pub fn verify_proof(&self, leaf: &Hash, proof: &Proof) -> bool {
         let leaf_idx = self.get_leaf_index(leaf).expect("leaf not found");
-        verify_proof(&self.root, leaf, proof, leaf_idx)
+        verify_proof(self.version, &self.root, leaf, proof, leaf_idx)
     }
 
     pub fn get_leaf_index(&self, leaf_hash: &Hash) -> Option<u64> {
-        self.levels
-            .first()?
-            .iter()
-            .position(|&hash| hash == *leaf_hash)
-            .map(|x| x as u64)
+        self.index.get(leaf_hash).copied()
+    }
+}
+
+/// One leaf and its proof, with the root and size of the tree it came from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LeafProof {
+    pub version: TreeVersion,
+    pub root: Hash,
+    pub leaf_count: u32,
+    pub leaf_index: u64,
+    pub leaf: Hash,
+    pub proof: Proof,
+}
+
+impl LeafProof {
+    pub fn verify(&self) -> bool {
+        verify_proof(
+            self.version,
+            &self.root,
+            &self.leaf,
+            &self.proof,
+            self.leaf_index,
+        )
+    }
+}
+
+/// A proof for several leaves of one tree. Siblings shared by their paths,
+/// or computed from the leaves, appear once.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiProof {
+    /// Ascending, without duplicates.
+    pub leaf_indices: Vec<u64>,
+    /// The sibling nodes `verify_multi_proof` doesn't compute, level by level
+    /// from the leaves up, and left to right in a level.
+    pub nodes: Vec<Hash>,
+}
+
+impl MultiProof {
+    /// The same proof as `MerkleTree::multi_proof`, from single leaf proofs
+    /// of one tree, such as the ones read from a sharded tree blob. `None`
+    /// if there are no proofs or they're from different trees.
+    pub fn from_leaf_proofs(proofs: &[LeafProof]) -> Option<MultiProof> {
+        let first = proofs.first()?;
+        let same_tree = proofs.iter().all(|p| {
+            p.root == first.root
+                && p.leaf_count == first.leaf_count
+                && p.proof.len() == first.proof.len()
+        });
+        if !same_tree {
+            return None;
+        }
+
+        // Each known node keeps one of the proofs below it, which has the
+        // node's sibling at that level
+        let mut known: Vec<(u64, &LeafProof)> = proofs.iter().map(|p| (p.leaf_index, p)).collect();
+        known.sort_unstable_by_key(|(index, _)| *index);
+        known.dedup_by_key(|(index, _)| *index);
+        let leaf_indices = known.iter().map(|(index, _)| *index).collect();
+
+        let mut nodes = Vec::new();
+        let mut level_len = first.leaf_count as u64;
+        for level in 0..first.proof.len() {
+            let mut parents = Vec::with_capacity(known.len());
+            let mut i = 0;
+            while i < known.len() {
+                let (index, proof) = known[i];
+                if !index.is_multiple_of(2) {
+                    nodes.push(proof.proof[level]);
+                } else if known.get(i + 1).map(|(next, _)| *next) == Some(index + 1) {
+                    i += 1;
+                } else if index + 1 < level_len {
+                    nodes.push(proof.proof[level]);
+                }
+                parents.push((index / 2, proof));
+                i += 1;
+            }
+            known = parents;
+            level_len = level_len.div_ceil(2);
+        }
+
+        Some(MultiProof {
+            leaf_indices,
+            nodes,
+        })
+    }
+
+    /// The proof's leaves from a tree of `leaf_count` leaves, in the order
+    /// of `leaf_indices`.
+    pub fn verify(
+        &self,
+        version: TreeVersion,
+        root: &Hash,
+        leaf_count: u32,
+        leaves: &[Hash],
+    ) -> bool {
+        verify_multi_proof(version, root, leaf_count, leaves, self)
     }
 }
 
+/// A v1 node, see `TreeVersion::hash_node`.
 fn hash_pair(left: &Hash, right: &Hash) -> Hash {
     let mut hasher = Blake2b::new();
     hasher.update(left);
@@ -86,19 +383,25 @@ fn hash_pair(left: &Hash, right: &Hash) -> Hash {
     hasher.finalize().into()
 }
 
-pub fn verify_proof(root: &Hash, leaf: &Hash, proof: &Proof, leaf_idx: u64) -> bool {
+pub fn verify_proof(
+    version: TreeVersion,
+    root: &Hash,
+    leaf: &Hash,
+    proof: &Proof,
+    leaf_idx: u64,
+) -> bool {
     let mut current_hash = *leaf;
     let mut current_idx = leaf_idx;
 
     // Iterate through the proof, hashing with each sibling
     for sibling in proof {
         // Determine if the current hash is left or right based on index
-        current_hash = if current_idx % 2 == 0 {
+        current_hash = if current_idx.is_multiple_of(2) {
             // Even index: current_hash is left, sibling is right
-            hash_pair(&current_hash, sibling)
+            version.hash_node(&current_hash, sibling)
         } else {
             // Odd index: sibling is left, current_hash is right
-            hash_pair(sibling, &current_hash)
+            version.hash_node(sibling, &current_hash)
         };
 
         // Move to the parent index
@@ -109,6 +412,63 @@ pub fn verify_proof(root: &Hash, leaf: &Hash, proof: &Proof, leaf_idx: u64) -> b
     current_hash == *root
 }
 
+/// Mirrors `drop::verify_multi_proof` in `sources/drop.move`. The leaf count
+/// is needed to know which nodes end an odd level.
+pub fn verify_multi_proof(
+    version: TreeVersion,
+    root: &Hash,
+    leaf_count: u32,
+    leaves: &[Hash],
+    proof: &MultiProof,
+) -> bool {
+    let indices = &proof.leaf_indices;
+    if leaves.is_empty() || leaves.len() != indices.len() {
+        return false;
+    }
+    if indices.windows(2).any(|w| w[0] >= w[1]) || indices[indices.len() - 1] >= leaf_count as u64 {
+        return false;
+    }
+
+    let mut known: Vec<(u64, Hash)> = indices
+        .iter()
+        .copied()
+        .zip(leaves.iter().copied())
+        .collect();
+    let mut nodes = proof.nodes.iter();
+    let mut len = leaf_count as u64;
+    while len > 1 {
+        let mut parents = Vec::with_capacity(known.len());
+        let mut i = 0;
+        while i < known.len() {
+            let (index, hash) = known[i];
+            let parent = if !index.is_multiple_of(2) {
+                let Some(sibling) = nodes.next() else {
+                    return false;
+                };
+                version.hash_node(sibling, &hash)
+            } else if let Some((_, right)) = known.get(i + 1).filter(|(next, _)| *next == index + 1)
+            {
+                // Both children are known
+                i += 1;
+                version.hash_node(&hash, right)
+            } else if index + 1 < len {
+                let Some(sibling) = nodes.next() else {
+                    return false;
+                };
+                version.hash_node(&hash, sibling)
+            } else {
+                version.hash_node(&hash, &hash)
+            };
+            parents.push((index / 2, parent));
+            i += 1;
+        }
+        known = parents;
+        len = len.div_ceil(2);
+    }
+
+    nodes.next().is_none() && known == [(0, *root)]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -231,6 +591,12 @@ mod tests {
         assert_eq!(tree.get_root(), deserialized.get_root());
         assert_eq!(tree.leaf_count, deserialized.leaf_count);
         assert_eq!(tree.levels, deserialized.levels);
+        assert_eq!(deserialized.get_leaf_index(&leaf2), Some(1));
+
+        let bytes = bcs::to_bytes(&tree).unwrap();
+        let deserialized: MerkleTree = bcs::from_bytes(&bytes).unwrap();
+        assert_eq!(deserialized, tree);
+        assert_eq!(deserialized.get_leaf_index(&leaf1), Some(0));
     }
 
     fn test_merkle_tree_large_leaves_impl(leaves: Vec<Hash>) -> Result<(), TestCaseError> {
@@ -272,4 +638,116 @@ mod tests {
             test_merkle_tree_large_leaves_impl(leaves)?;
         }
     }
+
+    #[test]
+    fn test_multi_proof() {
+        let leaves: Vec<Hash> = (0..5u8).map(|i| create_hash(&[i])).collect();
+        let tree = MerkleTree::new(&leaves).unwrap();
+
+        let proof = tree.multi_proof(&[4, 1, 2, 1]).unwrap();
+        assert_eq!(proof.leaf_indices, vec![1, 2, 4]);
+        // Leaf 0 and 3 at the bottom, then nothing: leaf 4 pairs with itself
+        // and the level above it is fully known
+        assert_eq!(proof.nodes, vec![leaves[0], leaves[3]]);
+        let proved = [leaves[1], leaves[2], leaves[4]];
+        assert!(proof.verify(TreeVersion::V1, &tree.get_root(), 5, &proved));
+
+        assert!(!proof.verify(TreeVersion::V1, &tree.get_root(), 6, &proved));
+        assert!(!proof.verify(
+            TreeVersion::V1,
+            &tree.get_root(),
+            5,
+            &[leaves[1], leaves[2], leaves[3]]
+        ));
+        assert!(!proof.verify(TreeVersion::V1, &tree.get_root(), 5, &proved[..2]));
+        let mut extra = proof.clone();
+        extra.nodes.push(leaves[0]);
+        assert!(!extra.verify(TreeVersion::V1, &tree.get_root(), 5, &proved));
+
+        assert_eq!(tree.multi_proof(&[]), None);
+        assert_eq!(tree.multi_proof(&[5]), None);
+    }
+
+    proptest! {
+        #[test]
+        fn test_multi_proof_matches_single_proofs(
+            leaves in prop::collection::vec(arb_hash(), 2..200),
+            picks in prop::collection::vec(any::<prop::sample::Index>(), 1..20),
+        ) {
+            let tree = MerkleTree::new(&leaves).unwrap();
+            let indices: Vec<u64> = picks.iter().map(|pick| pick.index(leaves.len()) as u64).collect();
+            let proof = tree.multi_proof(&indices).unwrap();
+            let proved: Vec<Hash> = proof.leaf_indices.iter().map(|i| leaves[*i as usize]).collect();
+            prop_assert!(proof.verify(TreeVersion::V1, &tree.get_root(), tree.leaf_count, &proved));
+            let singles: Vec<_> = indices.iter().map(|i| tree.leaf_proof(*i).unwrap()).collect();
+            prop_assert_eq!(MultiProof::from_leaf_proofs(&singles), Some(proof.clone()));
+
+            // Never more nodes than the single proofs, and the same for one leaf
+            // up to the odd nodes paired with themselves
+            let singles: usize = proof
+                .leaf_indices
+                .iter()
+                .map(|i| tree.leaf_proof(*i).unwrap())
+                .inspect(|single| assert!(single.verify()))
+                .map(|single| single.proof.len())
+                .sum();
+            prop_assert!(proof.nodes.len() <= singles);
+
+            if proof.leaf_indices.len() == 1 {
+                let single = tree.leaf_proof(proof.leaf_indices[0]).unwrap();
+                let expected: Vec<Hash> = tree
+                    .levels
+                    .iter()
+                    .zip(&single.proof)
+                    .enumerate()
+                    .filter(|(level, (nodes, _))| {
+                        let index = (single.leaf_index >> level) as usize;
+                        index ^ 1 < nodes.len()
+                    })
+                    .map(|(_, (_, sibling))| *sibling)
+                    .collect();
+                prop_assert_eq!(&proof.nodes, &expected);
+            }
+
+            let mut tampered = proof.clone();
+            if let Some(node) = tampered.nodes.first_mut() {
+                node[0] ^= 1;
+                prop_assert!(!tampered.verify(TreeVersion::V1, &tree.get_root(), tree.leaf_count, &proved));
+            }
+        }
+    }
+
+    #[test]
+    fn test_tree_versions() {
+        let leaves: Vec<Hash> = (0..5u8).map(|i| create_hash(&[i])).collect();
+        let v1 = MerkleTree::new(&leaves).unwrap();
+        let v2 = MerkleTree::with_version(&leaves, TreeVersion::V2).unwrap();
+        assert_ne!(v1.get_root(), v2.get_root());
+        assert_eq!(
+            v2.levels[1][0],
+            create_hash(&[&[NODE_TAG][..], &leaves[0], &leaves[1]].concat())
+        );
+
+        let proof = v2.leaf_proof(3).unwrap();
+        assert_eq!(proof.version, TreeVersion::V2);
+        assert!(proof.verify());
+        assert!(!verify_proof(
+            TreeVersion::V1,
+            &proof.root,
+            &proof.leaf,
+            &proof.proof,
+            3
+        ));
+        let (_, proof) = v2.get_proof(&leaves[4]);
+        assert!(v2.verify_proof(&leaves[4], &proof));
+
+        let multi = v2.multi_proof(&[0, 3]).unwrap();
+        assert!(multi.verify(TreeVersion::V2, &v2.get_root(), 5, &[leaves[0], leaves[3]]));
+        assert!(!multi.verify(TreeVersion::V1, &v2.get_root(), 5, &[leaves[0], leaves[3]]));
+
+        assert_eq!("v2".parse::<TreeVersion>().unwrap(), TreeVersion::V2);
+        assert_eq!("1".parse::<TreeVersion>().unwrap(), TreeVersion::V1);
+        assert!("3".parse::<TreeVersion>().is_err());
+        assert_eq!(serde_json::to_string(&TreeVersion::V2).unwrap(), "2");
+    }
 }
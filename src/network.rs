@@ -0,0 +1,266 @@
+use crate::keystore::SuiConfig;
+use crate::sui::GasConfig;
+use crate::walrus::WalrusClient;
+use anyhow::anyhow;
+use std::str::FromStr;
+use sui_sdk_types::{Address, ObjectId};
+use url::Url;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Devnet,
+    Localnet,
+}
+
+impl Network {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Devnet => "devnet",
+            Network::Localnet => "localnet",
+        }
+    }
+
+    pub fn graphql_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://sui-mainnet.mystenlabs.com/graphql",
+            Network::Testnet => "https://sui-testnet.mystenlabs.com/graphql",
+            Network::Devnet => "https://sui-devnet.mystenlabs.com/graphql",
+            Network::Localnet => "http://localhost:9125/graphql",
+        }
+    }
+
+    /// Published `large` package, if there is one on this network.
+    pub fn package_id(&self) -> Option<&'static str> {
+        match self {
+            Network::Mainnet => {
+                Some("0x7daa0be72e7c857f40f8aefa74ac835dccfa4e25ec40a92665e54249d93d3892")
+            }
+            Network::Testnet => {
+                Some("0x5cccbfec0ef491993f5b2aa19b98845476ad720254c2e758254e23dbe547b94d")
+            }
+            Network::Devnet | Network::Localnet => None,
+        }
+    }
+
+    /// Campaign used when no drop ID is given.
+    pub fn default_drop(&self) -> Option<&'static str> {
+        match self {
+            // A pre-created testnet campaign
+            Network::Testnet => {
+                Some("0xdda2402ee7e7a4cb0a5a68692e9dac087be029bbd7d518e189121387a12b71b1")
+            }
+            Network::Mainnet | Network::Devnet | Network::Localnet => None,
+        }
+    }
+
+    pub fn walrus_publisher(&self) -> Option<&'static str> {
+        match self {
+            Network::Testnet => Some(crate::walrus::TESTNET_PUBLISHER),
+            // There is no public mainnet publisher
+            Network::Mainnet | Network::Devnet | Network::Localnet => None,
+        }
+    }
+
+    pub fn walrus_aggregator(&self) -> Option<&'static str> {
+        match self {
+            Network::Mainnet => Some(crate::walrus::MAINNET_AGGREGATOR),
+            Network::Testnet => Some(crate::walrus::TESTNET_AGGREGATOR),
+            Network::Devnet | Network::Localnet => None,
+        }
+    }
+
+    /// Walrus `System` object, which holds the current storage epoch.
+    pub fn walrus_system_object(&self) -> Option<&'static str> {
+        match self {
+            Network::Mainnet => {
+                Some("0x2134d52768ea07e8c43570ef975eb3e4c27a39fa6396bef985b5abc58d03ddd2")
+            }
+            Network::Testnet => {
+                Some("0x6c2547cbbc38025cf3adac45f63cb0a8d12ecf777cdc75a4971612bf97fdf6af")
+            }
+            Network::Devnet | Network::Localnet => None,
+        }
+    }
+
+    /// Length of a Walrus storage epoch.
+    pub fn walrus_epoch_days(&self) -> Option<u32> {
+        match self {
+            Network::Mainnet => Some(14),
+            Network::Testnet => Some(1),
+            Network::Devnet | Network::Localnet => None,
+        }
+    }
+
+    /// The network of the Sui client config's active env.
+    pub fn from_sui_config(config: &SuiConfig) -> Option<Self> {
+        config
+            .active_env
+            .as_ref()
+            .and_then(|alias| alias.parse().ok())
+            .or_else(|| Self::from_rpc(&config.active_rpc()?))
+    }
+
+    /// Guess the network from a fullnode RPC URL, as found in the Sui client config.
+    pub fn from_rpc(rpc: &Url) -> Option<Self> {
+        let host = rpc.host_str()?;
+        if host == "localhost" || host == "127.0.0.1" || host == "0.0.0.0" {
+            return Some(Network::Localnet);
+        }
+        [Network::Mainnet, Network::Testnet, Network::Devnet]
+            .into_iter()
+            .find(|network| host.contains(network.name()))
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" => Ok(Network::Mainnet),
+            "testnet" => Ok(Network::Testnet),
+            "devnet" => Ok(Network::Devnet),
+            "localnet" | "local" | "localhost" => Ok(Network::Localnet),
+            _ => Err(anyhow!("unknown network: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Network {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Endpoints and object IDs for a deployment of the protocol. Starts from the
+/// built-in table for a network, with any field overridable.
+#[derive(Clone, Debug)]
+pub struct NetworkConfig {
+    pub network: Network,
+    pub graphql_url: Url,
+    pub package_id: Option<Address>,
+    /// Upgraded package to call functions on. Types keep the original
+    /// `package_id` across upgrades.
+    pub latest_package_id: Option<Address>,
+    pub default_drop: Option<ObjectId>,
+    pub walrus_publisher: Option<Url>,
+    pub walrus_aggregator: Option<Url>,
+    pub walrus_system: Option<ObjectId>,
+    pub gas: GasConfig,
+}
+
+impl NetworkConfig {
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            graphql_url: Url::parse(network.graphql_url()).expect("bad graphql url"),
+            package_id: network
+                .package_id()
+                .map(|id| Address::from_str(id).expect("bad package id")),
+            latest_package_id: None,
+            default_drop: network
+                .default_drop()
+                .map(|id| ObjectId::from_str(id).expect("bad drop id")),
+            walrus_publisher: network
+                .walrus_publisher()
+                .map(|url| Url::parse(url).expect("bad publisher url")),
+            walrus_aggregator: network
+                .walrus_aggregator()
+                .map(|url| Url::parse(url).expect("bad aggregator url")),
+            walrus_system: network
+                .walrus_system_object()
+                .map(|id| ObjectId::from_str(id).expect("bad system object id")),
+            gas: GasConfig::default(),
+        }
+    }
+
+    pub fn client(&self) -> anyhow::Result<sui_graphql_client::Client> {
+        let client = sui_graphql_client::Client::new(self.graphql_url.as_str())?;
+        Ok(client)
+    }
+
+    pub fn package_id(&self) -> anyhow::Result<Address> {
+        self.package_id.ok_or(anyhow!(
+            "No Large package known on {}, set a package ID",
+            self.network
+        ))
+    }
+
+    /// Package for Move calls: the latest upgrade if one is set, otherwise
+    /// the original.
+    pub fn call_package_id(&self) -> anyhow::Result<Address> {
+        match self.latest_package_id {
+            Some(id) => Ok(id),
+            None => self.package_id(),
+        }
+    }
+
+    pub fn default_drop(&self) -> anyhow::Result<ObjectId> {
+        self.default_drop.ok_or(anyhow!(
+            "No default campaign on {}, pass a drop ID",
+            self.network
+        ))
+    }
+
+    pub fn walrus(&self) -> anyhow::Result<WalrusClient> {
+        let aggregator = self
+            .walrus_aggregator
+            .clone()
+            .ok_or(anyhow!("No Walrus aggregator known on {}", self.network))?;
+        Ok(match &self.walrus_publisher {
+            Some(publisher) => WalrusClient::new(publisher.clone(), aggregator),
+            None => WalrusClient::read_only(aggregator),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_table() {
+        for network in [
+            Network::Mainnet,
+            Network::Testnet,
+            Network::Devnet,
+            Network::Localnet,
+        ] {
+            let config = NetworkConfig::new(network);
+            assert_eq!(network.name().parse::<Network>().unwrap(), network);
+            assert_eq!(
+                Network::from_rpc(
+                    &Url::parse(&format!("https://fullnode.{}.sui.io:443", network)).unwrap()
+                ),
+                (network != Network::Localnet).then_some(network)
+            );
+            assert!(config.client().is_ok());
+        }
+
+        assert_eq!(
+            Network::from_rpc(&Url::parse("http://127.0.0.1:9000").unwrap()),
+            Some(Network::Localnet)
+        );
+        assert!(NetworkConfig::new(Network::Testnet).default_drop().is_ok());
+        assert!(NetworkConfig::new(Network::Mainnet).package_id().is_ok());
+        assert!(NetworkConfig::new(Network::Localnet).package_id().is_err());
+        assert!(NetworkConfig::new(Network::Localnet)
+            .call_package_id()
+            .is_err());
+
+        let mut upgraded = NetworkConfig::new(Network::Mainnet);
+        assert_eq!(
+            upgraded.call_package_id().unwrap(),
+            upgraded.package_id().unwrap()
+        );
+        upgraded.latest_package_id = Some(Address::ZERO);
+        assert_eq!(upgraded.call_package_id().unwrap(), Address::ZERO);
+        assert_ne!(upgraded.package_id().unwrap(), Address::ZERO);
+        assert!(NetworkConfig::new(Network::Localnet).walrus().is_err());
+    }
+}
@@ -0,0 +1,442 @@
+//! Sharded merkle tree blob, so a proof can be read without downloading the
+//! whole tree.
+//!
+//! The levels are cut into bands of `shard_height` levels, and each band
+//! into shards holding one subtree: up to `2^shard_height` nodes of the
+//! band's lowest level and the nodes above them. A leaf's path passes
+//! through one shard per band, so a proof takes a range read per band
+//! instead of the whole tree.
+//!
+//! ```text
+//! manifest      MANIFEST_LEN bytes
+//!   magic         4 bytes   "LRGT"
+//!   version       u16 LE
+//!   shard_height  u8
+//!   tree_version  u8        hashing of the nodes, 0 in older blobs means v1
+//!   leaf_count    u32 LE
+//!   root          32 bytes
+//! shards        band by band, shard by shard, level by level, 32 bytes a node
+//! ```
+//!
+//! Shard offsets follow from `leaf_count` and `shard_height`, so the
+//! manifest is the only thing to read before the shards.
+
+use crate::merkle::{Hash, LeafProof, MerkleTree, TreeVersion};
+use crate::store::BlobStore;
+use anyhow::{anyhow, Context};
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+
+pub const MAGIC: &[u8; 4] = b"LRGT";
+pub const VERSION: u16 = 1;
+
+pub const MANIFEST_LEN: usize = 44;
+
+/// 4096 leaves a shard, so a 1M wallet proof reads two shards of ~256KB.
+pub const DEFAULT_SHARD_HEIGHT: u8 = 12;
+
+const NODE_LEN: u64 = 32;
+
+pub fn is_sharded(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub shard_height: u8,
+    pub tree_version: TreeVersion,
+    pub leaf_count: u32,
+    pub root: Hash,
+}
+
+impl Manifest {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(MANIFEST_LEN);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.push(self.shard_height);
+        data.push(self.tree_version.into());
+        data.extend_from_slice(&self.leaf_count.to_le_bytes());
+        data.extend_from_slice(&self.root);
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < MANIFEST_LEN || !is_sharded(data) {
+            return Err(anyhow!("Not a sharded tree blob"));
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != VERSION {
+            return Err(anyhow!("Unsupported tree blob version: {}", version));
+        }
+        let tree_version = match data[7] {
+            0 => TreeVersion::V1,
+            version => TreeVersion::try_from(version)?,
+        };
+        let manifest = Self {
+            shard_height: data[6],
+            tree_version,
+            leaf_count: u32::from_le_bytes(data[8..12].try_into()?),
+            root: data[12..MANIFEST_LEN].try_into()?,
+        };
+        if !(1..=32).contains(&manifest.shard_height) || manifest.leaf_count < 2 {
+            return Err(anyhow!("Malformed tree blob manifest"));
+        }
+        Ok(manifest)
+    }
+
+    fn layout(&self) -> Layout {
+        let mut lens = vec![self.leaf_count as u64];
+        while let Some(&len) = lens.last().filter(|len| **len > 1) {
+            lens.push(len.div_ceil(2));
+        }
+        Layout {
+            height: self.shard_height as usize,
+            lens,
+        }
+    }
+
+    /// Size of the whole blob.
+    pub fn blob_len(&self) -> u64 {
+        MANIFEST_LEN as u64 + self.layout().lens.iter().sum::<u64>() * NODE_LEN
+    }
+}
+
+/// Where each node lives, from the level lengths alone.
+struct Layout {
+    height: usize,
+    /// Nodes per level, leaves first, root last.
+    lens: Vec<u64>,
+}
+
+impl Layout {
+    fn bands(&self) -> Range<usize> {
+        0..self.lens.len().div_ceil(self.height)
+    }
+
+    fn band_levels(&self, band: usize) -> Range<usize> {
+        let start = band * self.height;
+        start..(start + self.height).min(self.lens.len())
+    }
+
+    fn shard_count(&self, band: usize) -> u64 {
+        let base = self.lens[band * self.height];
+        base.div_ceil(1 << self.height)
+    }
+
+    /// Nodes of `shard` at `level`. Only the last shard of a band is cut short.
+    fn shard_level(&self, band: usize, shard: u64, level: usize) -> Range<u64> {
+        let width = (self.height - (level - band * self.height)) as u32;
+        let start = shard << width;
+        let end = ((shard + 1) << width).min(self.lens[level]);
+        start..end
+    }
+
+    /// Byte range of `shard` in the blob.
+    fn shard_range(&self, band: usize, shard: u64) -> Range<u64> {
+        let levels = self.band_levels(band);
+        let band_start: u64 = self.lens[..levels.start].iter().sum();
+        let full: u64 = levels
+            .clone()
+            .map(|level| self.shard_level(band, 0, level).end)
+            .sum();
+        let len: u64 = levels
+            .map(|level| {
+                let nodes = self.shard_level(band, shard, level);
+                nodes.end - nodes.start
+            })
+            .sum();
+        let start = MANIFEST_LEN as u64 + (band_start + shard * full) * NODE_LEN;
+        start..start + len * NODE_LEN
+    }
+}
+
+pub fn encode(tree: &MerkleTree, shard_height: u8) -> anyhow::Result<Vec<u8>> {
+    let levels: Vec<&[Hash]> = tree.levels().iter().map(Vec::as_slice).collect();
+    encode_levels(&levels, tree.version, shard_height)
+}
+
+/// Encode a tree's levels, leaves first, e.g. from `builder::TreeLevels`.
+pub fn encode_levels(
+    levels: &[&[Hash]],
+    tree_version: TreeVersion,
+    shard_height: u8,
+) -> anyhow::Result<Vec<u8>> {
+    let nodes: usize = levels.iter().map(|level| level.len()).sum();
+    let mut data = Vec::with_capacity(MANIFEST_LEN + nodes * NODE_LEN as usize);
+    write_levels(levels, tree_version, shard_height, &mut data)?;
+    Ok(data)
+}
+
+/// Like `encode_levels`, into an unlinked temporary file in `dir` that's
+/// mapped back for upload, so a tree spilled to disk is never copied into
+/// memory.
+pub fn encode_levels_mapped(
+    levels: &[&[Hash]],
+    tree_version: TreeVersion,
+    shard_height: u8,
+    dir: &Path,
+) -> anyhow::Result<memmap2::Mmap> {
+    let file = tempfile::tempfile_in(dir)
+        .with_context(|| format!("Failed to create tree blob file in {}", dir.display()))?;
+    let mut out = std::io::BufWriter::new(&file);
+    write_levels(levels, tree_version, shard_height, &mut out)?;
+    out.flush()?;
+    drop(out);
+    // SAFETY: the file is unlinked and only reachable through this map
+    Ok(unsafe { memmap2::Mmap::map(&file)? })
+}
+
+/// Write the sharded blob of a tree's levels, leaves first.
+pub fn write_levels(
+    levels: &[&[Hash]],
+    tree_version: TreeVersion,
+    shard_height: u8,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    if !(1..=32).contains(&shard_height) {
+        return Err(anyhow!("Shard height must be between 1 and 32"));
+    }
+    let (Some(leaves), Some([root])) = (levels.first(), levels.last()) else {
+        return Err(anyhow!("malformed tree levels"));
+    };
+    let manifest = Manifest {
+        shard_height,
+        tree_version,
+        leaf_count: u32::try_from(leaves.len())?,
+        root: *root,
+    };
+    let layout = manifest.layout();
+    let lens = levels.iter().map(|level| level.len() as u64);
+    if layout.lens.len() != levels.len() || !layout.lens.iter().copied().eq(lens) {
+        return Err(anyhow!("malformed tree levels"));
+    }
+    out.write_all(&manifest.encode())?;
+    for band in layout.bands() {
+        for shard in 0..layout.shard_count(band) {
+            for level in layout.band_levels(band) {
+                let nodes = layout.shard_level(band, shard, level);
+                out.write_all(
+                    levels[level][nodes.start as usize..nodes.end as usize].as_flattened(),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn decode(data: &[u8]) -> anyhow::Result<MerkleTree> {
+    let manifest = Manifest::decode(data)?;
+    if data.len() as u64 != manifest.blob_len() {
+        return Err(anyhow!(
+            "Tree blob is {} bytes, expected {}",
+            data.len(),
+            manifest.blob_len()
+        ));
+    }
+    let layout = manifest.layout();
+    let mut levels: Vec<Vec<Hash>> = layout
+        .lens
+        .iter()
+        .map(|len| Vec::with_capacity(*len as usize))
+        .collect();
+    let mut nodes = data[MANIFEST_LEN..].chunks_exact(NODE_LEN as usize);
+    for band in layout.bands() {
+        for shard in 0..layout.shard_count(band) {
+            for level in layout.band_levels(band) {
+                let range = layout.shard_level(band, shard, level);
+                for node in nodes.by_ref().take((range.end - range.start) as usize) {
+                    levels[level].push(node.try_into()?);
+                }
+            }
+        }
+    }
+
+    let tree = MerkleTree::from_levels(levels, manifest.tree_version)?;
+    if tree.get_root() != manifest.root {
+        return Err(anyhow!("Tree blob root doesn't match its manifest"));
+    }
+    Ok(tree)
+}
+
+/// The proof for one leaf, reading only the shards on the leaf's path.
+/// `manifest` is the blob's, already read by the caller.
+pub async fn read_proof(
+    store: &dyn BlobStore,
+    blob_id: &str,
+    manifest: &Manifest,
+    leaf_index: u64,
+) -> anyhow::Result<LeafProof> {
+    if leaf_index >= manifest.leaf_count as u64 {
+        return Err(anyhow!(
+            "Leaf index {} is out of range, the tree has {} leaves",
+            leaf_index,
+            manifest.leaf_count
+        ));
+    }
+    let layout = manifest.layout();
+    let root_level = layout.lens.len() - 1;
+
+    let mut leaf = None;
+    let mut proof = Vec::with_capacity(root_level);
+    for band in layout.bands() {
+        let levels = layout.band_levels(band);
+        // A band with only the root has nothing to read
+        if levels.start == root_level {
+            break;
+        }
+        let shard = (leaf_index >> levels.start) >> layout.height;
+        let range = layout.shard_range(band, shard);
+        let data = store.get_range(blob_id, range.clone()).await?;
+        if data.len() as u64 != range.end - range.start {
+            return Err(anyhow!("Short read of tree shard"));
+        }
+
+        let mut offset = 0;
+        for level in levels {
+            let nodes = layout.shard_level(band, shard, level);
+            let node = |index: u64| -> anyhow::Result<Hash> {
+                let at = (offset + index - nodes.start) as usize * NODE_LEN as usize;
+                Ok(data[at..at + NODE_LEN as usize].try_into()?)
+            };
+            let index = leaf_index >> level;
+            if level == 0 {
+                leaf = Some(node(index)?);
+            }
+            if level < root_level {
+                // The last node of an odd level is paired with itself
+                let sibling = index ^ 1;
+                proof.push(if sibling < layout.lens[level] {
+                    node(sibling)?
+                } else {
+                    node(index)?
+                });
+            }
+            offset += nodes.end - nodes.start;
+        }
+    }
+
+    Ok(LeafProof {
+        version: manifest.tree_version,
+        root: manifest.root,
+        leaf_count: manifest.leaf_count,
+        leaf_index,
+        leaf: leaf.ok_or(anyhow!("Leaf missing from tree blob"))?,
+        proof,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+    use blake2::{Blake2b, Digest};
+    use proptest::prelude::*;
+
+    fn tree(leaf_count: u32) -> MerkleTree {
+        versioned_tree(leaf_count, TreeVersion::V1)
+    }
+
+    fn versioned_tree(leaf_count: u32, version: TreeVersion) -> MerkleTree {
+        let leaves: Vec<Hash> = (0..leaf_count)
+            .map(|i| Blake2b::digest(i.to_le_bytes()).into())
+            .collect();
+        MerkleTree::with_version(&leaves, version).unwrap()
+    }
+
+    #[test]
+    fn test_manifest() {
+        let tree = tree(5);
+        let data = encode(&tree, 2).unwrap();
+        let manifest = Manifest::decode(&data).unwrap();
+        assert_eq!(manifest.leaf_count, 5);
+        assert_eq!(manifest.root, tree.get_root());
+        // 5 + 3 + 2 + 1 nodes
+        assert_eq!(data.len(), MANIFEST_LEN + 11 * 32);
+        assert_eq!(manifest.blob_len(), data.len() as u64);
+
+        assert!(decode(&data[..data.len() - 32]).is_err());
+        let mut bad_root = data.clone();
+        bad_root[12] ^= 1;
+        assert!(decode(&bad_root).is_err());
+        assert!(Manifest::decode(&bcs::to_bytes(&tree).unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tree_version() {
+        let v2 = versioned_tree(9, TreeVersion::V2);
+        let data = encode(&v2, 2).unwrap();
+        assert_eq!(data[7], 2);
+        assert_eq!(
+            Manifest::decode(&data).unwrap().tree_version,
+            TreeVersion::V2
+        );
+        assert_eq!(decode(&data).unwrap(), v2);
+
+        let store = MemoryStore::new();
+        let blob = store.put(data.clone().into(), 1, true).await.unwrap();
+        let manifest = Manifest::decode(&data).unwrap();
+        let proof = read_proof(&store, &blob.blob_id, &manifest, 4)
+            .await
+            .unwrap();
+        assert_eq!(proof.version, TreeVersion::V2);
+        assert!(proof.verify());
+
+        // Blobs from before the version byte read as v1
+        let mut legacy = encode(&tree(9), 2).unwrap();
+        legacy[7] = 0;
+        assert_eq!(decode(&legacy).unwrap(), tree(9));
+        legacy[7] = 9;
+        assert!(Manifest::decode(&legacy).is_err());
+    }
+
+    #[test]
+    fn test_encode_mapped() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = tree(300);
+        let levels: Vec<&[Hash]> = tree.levels().iter().map(Vec::as_slice).collect();
+        let mapped = encode_levels_mapped(&levels, tree.version, 3, dir.path()).unwrap();
+        assert_eq!(&mapped[..], encode(&tree, 3).unwrap().as_slice());
+    }
+
+    #[tokio::test]
+    async fn test_read_proof() {
+        let store = MemoryStore::new();
+        let tree = tree(1_000);
+        let data = encode(&tree, 3).unwrap();
+        let manifest = Manifest::decode(&data).unwrap();
+        let blob = store.put(data.clone().into(), 1, true).await.unwrap();
+
+        for leaf_index in [0, 1, 7, 8, 500, 998, 999] {
+            let proof = read_proof(&store, &blob.blob_id, &manifest, leaf_index)
+                .await
+                .unwrap();
+            assert_eq!(proof, tree.leaf_proof(leaf_index).unwrap());
+            assert!(proof.verify());
+        }
+        assert!(read_proof(&store, &blob.blob_id, &manifest, 1_000)
+            .await
+            .is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn test_sharded_roundtrip(leaf_count in 2u32..600, shard_height in 1u8..6) {
+            let tree = tree(leaf_count);
+            let data = encode(&tree, shard_height).unwrap();
+            prop_assert_eq!(decode(&data).unwrap(), tree.clone());
+
+            let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+            let store = MemoryStore::new();
+            let blob = rt.block_on(store.put(data.clone().into(), 1, true)).unwrap();
+            let manifest = Manifest::decode(&data).unwrap();
+            for leaf_index in [0, leaf_count as u64 / 2, leaf_count as u64 - 1] {
+                let proof = rt
+                    .block_on(read_proof(&store, &blob.blob_id, &manifest, leaf_index))
+                    .unwrap();
+                prop_assert_eq!(proof, tree.leaf_proof(leaf_index).unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,425 @@
+use crate::ffi;
+use crate::keystore::{Keystore, PrivateKey, SuiConfig};
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use base64::Engine;
+use std::str::FromStr;
+use sui_crypto::SuiVerifier;
+use sui_sdk_types::{Address, SimpleSignature, Transaction, UserSignature};
+use url::Url;
+
+/// Something that can sign transactions for a wallet.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    async fn address(&self) -> anyhow::Result<Address>;
+
+    async fn sign_tx(&self, tx: &Transaction) -> anyhow::Result<UserSignature>;
+}
+
+/// Which signer to use, picked with `--signer` or the `signer` config key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SignerKind {
+    /// Active wallet in the Sui client config, signed in-process.
+    #[default]
+    Keystore,
+    /// `sui keytool sign`, requires the Sui CLI.
+    SuiCli,
+    /// A `suiprivkey...` key in $LARGE_PRIVATE_KEY.
+    Env,
+    /// A signing service at `signer_url`.
+    Remote,
+}
+
+impl SignerKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SignerKind::Keystore => "keystore",
+            SignerKind::SuiCli => "sui-cli",
+            SignerKind::Env => "env",
+            SignerKind::Remote => "remote",
+        }
+    }
+}
+
+impl FromStr for SignerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keystore" => Ok(SignerKind::Keystore),
+            "sui-cli" => Ok(SignerKind::SuiCli),
+            "env" => Ok(SignerKind::Env),
+            "remote" => Ok(SignerKind::Remote),
+            _ => Err(anyhow!(
+                "unknown signer: {}, expected keystore, sui-cli, env or remote",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for SignerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Signs with `sui keytool sign`, using the active Sui CLI wallet.
+pub struct CliSigner;
+
+#[async_trait]
+impl Signer for CliSigner {
+    async fn address(&self) -> anyhow::Result<Address> {
+        ffi::current_wallet().await
+    }
+
+    async fn sign_tx(&self, tx: &Transaction) -> anyhow::Result<UserSignature> {
+        let wallet = self.address().await?;
+        ffi::sign_tx(&wallet, tx).await
+    }
+}
+
+/// Signs in-process with a key from a `sui.keystore` file.
+pub struct KeystoreSigner {
+    keystore: Keystore,
+    address: Address,
+}
+
+impl KeystoreSigner {
+    pub fn new(keystore: Keystore, address: Address) -> anyhow::Result<Self> {
+        if keystore.get(&address).is_none() {
+            return Err(keystore.missing(&address));
+        }
+        Ok(Self { keystore, address })
+    }
+
+    /// The active wallet of the Sui client config.
+    pub fn from_sui_config() -> anyhow::Result<Self> {
+        let config = SuiConfig::load()?;
+        Self::new(config.load_keystore()?, config.active_wallet()?)
+    }
+}
+
+#[async_trait]
+impl Signer for KeystoreSigner {
+    async fn address(&self) -> anyhow::Result<Address> {
+        Ok(self.address)
+    }
+
+    async fn sign_tx(&self, tx: &Transaction) -> anyhow::Result<UserSignature> {
+        self.keystore.sign_tx(&self.address, tx)
+    }
+}
+
+/// Signs in-process with a single `suiprivkey...` key.
+pub struct KeySigner {
+    key: PrivateKey,
+}
+
+impl KeySigner {
+    pub fn new(key: PrivateKey) -> Self {
+        Self { key }
+    }
+
+    /// Reads the key from `LARGE_PRIVATE_KEY`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_vars(std::env::vars())
+    }
+
+    fn from_vars(vars: impl Iterator<Item = (String, String)>) -> anyhow::Result<Self> {
+        #[derive(serde::Deserialize)]
+        struct KeyEnv {
+            private_key: String,
+        }
+
+        let env: KeyEnv = envy::prefixed("LARGE_")
+            .from_iter(vars)
+            .context("LARGE_PRIVATE_KEY is not set")?;
+        Ok(Self::new(PrivateKey::from_suiprivkey(&env.private_key)?))
+    }
+}
+
+#[async_trait]
+impl Signer for KeySigner {
+    async fn address(&self) -> anyhow::Result<Address> {
+        Ok(self.key.address())
+    }
+
+    async fn sign_tx(&self, tx: &Transaction) -> anyhow::Result<UserSignature> {
+        self.key.sign_tx(tx)
+    }
+}
+
+/// Delegates signing to an HTTP signing service.
+///
+/// `GET {url}/address` returns `{"address": "0x..."}`, and `POST {url}/sign`
+/// takes `{"address": "0x...", "tx_bytes": "<base64 BCS>"}` and returns
+/// `{"signature": "<base64 Sui signature>"}`. Signatures are checked against
+/// the transaction and the address before they're used.
+pub struct RemoteSigner {
+    http: reqwest::Client,
+    url: Url,
+    token: Option<String>,
+    address: Option<Address>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct AddressResponse {
+    pub address: Address,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct SignRequest {
+    pub address: Address,
+    pub tx_bytes: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+pub struct SignResponse {
+    pub signature: String,
+}
+
+impl RemoteSigner {
+    pub fn new(mut url: Url) -> Self {
+        // Without a trailing slash, `join` would replace the last segment
+        if !url.path().ends_with('/') {
+            url.set_path(&format!("{}/", url.path()));
+        }
+        Self {
+            http: reqwest::Client::new(),
+            url,
+            token: None,
+            address: None,
+        }
+    }
+
+    /// Sent as a bearer token with every request.
+    pub fn with_token(mut self, token: String) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Sign as this address instead of asking the service.
+    pub fn with_address(mut self, address: Address) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Reads `LARGE_SIGNER_URL`, and optionally `LARGE_SIGNER_TOKEN` and
+    /// `LARGE_SIGNER_ADDRESS`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        #[derive(serde::Deserialize)]
+        struct RemoteEnv {
+            signer_url: String,
+            signer_token: Option<String>,
+            signer_address: Option<String>,
+        }
+
+        let env: RemoteEnv = envy::prefixed("LARGE_")
+            .from_env()
+            .context("LARGE_SIGNER_URL is not set")?;
+
+        let mut signer = Self::new(Url::parse(&env.signer_url)?);
+        if let Some(token) = env.signer_token {
+            signer = signer.with_token(token);
+        }
+        if let Some(address) = env.signer_address {
+            signer = signer.with_address(address.parse()?);
+        }
+        Ok(signer)
+    }
+
+    fn request(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.token {
+            Some(token) => builder.bearer_auth(token),
+            None => builder,
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    async fn address(&self) -> anyhow::Result<Address> {
+        if let Some(address) = self.address {
+            return Ok(address);
+        }
+
+        let res: AddressResponse = self
+            .request(self.http.get(self.url.join("address")?))
+            .send()
+            .await
+            .context("Failed to reach remote signer")?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(res.address)
+    }
+
+    async fn sign_tx(&self, tx: &Transaction) -> anyhow::Result<UserSignature> {
+        let address = self.address().await?;
+        let body = SignRequest {
+            address,
+            tx_bytes: base64::engine::general_purpose::STANDARD.encode(bcs::to_bytes(tx)?),
+        };
+
+        let res: SignResponse = self
+            .request(self.http.post(self.url.join("sign")?))
+            .json(&body)
+            .send()
+            .await
+            .context("Failed to reach remote signer")?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let sig = UserSignature::from_base64(&res.signature)?;
+        sui_crypto::UserSignatureVerifier::new()
+            .verify_transaction(tx, &sig)
+            .context("Remote signer returned an invalid signature")?;
+        let signer = signature_address(&sig)?;
+        if signer != address {
+            return Err(anyhow!(
+                "Remote signer signed as {}, expected {}",
+                signer,
+                address
+            ));
+        }
+        Ok(sig)
+    }
+}
+
+/// The address whose key made `sig`.
+fn signature_address(sig: &UserSignature) -> anyhow::Result<Address> {
+    match sig {
+        UserSignature::Simple(SimpleSignature::Ed25519 { public_key, .. }) => {
+            Ok(public_key.derive_address())
+        }
+        UserSignature::Simple(SimpleSignature::Secp256k1 { public_key, .. }) => {
+            Ok(public_key.derive_address())
+        }
+        UserSignature::Simple(SimpleSignature::Secp256r1 { public_key, .. }) => {
+            Ok(public_key.derive_address())
+        }
+        UserSignature::Multisig(multisig) => Ok(multisig.committee().derive_address()),
+        _ => Err(anyhow!("Unsupported signature kind from remote signer")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keystore::tests::test_tx;
+    use axum::{
+        extract::State,
+        routing::{get, post},
+        Json, Router,
+    };
+    use std::sync::Arc;
+
+    const SECRET: [u8; 32] = [5; 32];
+
+    fn test_key() -> PrivateKey {
+        PrivateKey::from_bytes(&[[0].as_slice(), &SECRET].concat()).unwrap()
+    }
+
+    fn verify(tx: &Transaction, sig: &UserSignature) {
+        let PrivateKey::Ed25519(key) = test_key() else {
+            unreachable!()
+        };
+        key.verifying_key().verify_transaction(tx, sig).unwrap();
+    }
+
+    async fn spawn_signer() -> Url {
+        spawn_signer_at(test_key().address(), test_key(), "/").await
+    }
+
+    /// A signing service under `prefix` that claims to be `claimed` and signs
+    /// with `key`.
+    async fn spawn_signer_at(claimed: Address, key: PrivateKey, prefix: &str) -> Url {
+        type Service = State<Arc<(Address, PrivateKey)>>;
+
+        async fn address(State(service): Service) -> Json<AddressResponse> {
+            Json(AddressResponse { address: service.0 })
+        }
+
+        async fn sign(State(service): Service, Json(req): Json<SignRequest>) -> Json<SignResponse> {
+            let (address, key) = &*service;
+            assert_eq!(req.address, *address);
+            let bts = base64::engine::general_purpose::STANDARD
+                .decode(req.tx_bytes)
+                .unwrap();
+            let tx: Transaction = bcs::from_bytes(&bts).unwrap();
+            Json(SignResponse {
+                signature: key.sign_tx(&tx).unwrap().to_base64(),
+            })
+        }
+
+        let routes = Router::new()
+            .route("/address", get(address))
+            .route("/sign", post(sign))
+            .with_state(Arc::new((claimed, key)));
+        let app = match prefix {
+            "/" => routes,
+            prefix => Router::new().nest(prefix, routes),
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!(
+            "http://{}{}",
+            listener.local_addr().unwrap(),
+            prefix
+        ))
+        .unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer() {
+        let signer = RemoteSigner::new(spawn_signer().await);
+        let address = signer.address().await.unwrap();
+        assert_eq!(address, test_key().address());
+
+        let tx = test_tx(address);
+        let sig = signer.sign_tx(&tx).await.unwrap();
+        verify(&tx, &sig);
+    }
+
+    #[tokio::test]
+    async fn test_remote_signer_checks() {
+        // A base URL without a trailing slash keeps its last segment
+        let url = spawn_signer_at(test_key().address(), test_key(), "/signer/v1").await;
+        assert!(!url.path().ends_with('/'));
+        let signer = RemoteSigner::new(url);
+        let address = signer.address().await.unwrap();
+        assert_eq!(address, test_key().address());
+        verify(
+            &test_tx(address),
+            &signer.sign_tx(&test_tx(address)).await.unwrap(),
+        );
+
+        // A service signing with another key than the one asked for
+        let other = PrivateKey::from_bytes(&[[0].as_slice(), &[6; 32]].concat()).unwrap();
+        let url = spawn_signer_at(test_key().address(), other, "/").await;
+        let signer = RemoteSigner::new(url);
+        assert!(signer
+            .sign_tx(&test_tx(test_key().address()))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_env_key_signer() {
+        let encoded = crate::sui::suiprivkey_from_bytes(&SECRET).unwrap();
+        let vars = vec![("LARGE_PRIVATE_KEY".to_string(), encoded)];
+        let signer = KeySigner::from_vars(vars.into_iter()).unwrap();
+        let address = signer.address().await.unwrap();
+        assert_eq!(address, test_key().address());
+
+        let tx = test_tx(address);
+        let sig = signer.sign_tx(&tx).await.unwrap();
+        verify(&tx, &sig);
+
+        assert!(KeySigner::from_vars(std::iter::empty()).is_err());
+    }
+}
@@ -0,0 +1,510 @@
+use crate::ffi::{self, NewBlob};
+use crate::walrus::WalrusClient;
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use base64::Engine;
+use blake2::{Blake2b, Digest};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use sui_sdk_types::Address;
+
+/// Storage backend for campaign blobs.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Takes the blob by value, so uploads can send it without a copy.
+    async fn put(&self, value: Bytes, epochs: u32, deletable: bool) -> anyhow::Result<NewBlob>;
+
+    async fn get(&self, blob_id: &str) -> anyhow::Result<Vec<u8>>;
+
+    /// Bytes `range` of a blob. Stores that can't read part of a blob read
+    /// all of it, see `supports_ranges`.
+    async fn get_range(&self, blob_id: &str, range: Range<u64>) -> anyhow::Result<Vec<u8>> {
+        let data = self.get(blob_id).await?;
+        slice_range(&data, range)
+    }
+
+    /// Whether `get_range` reads only the requested bytes.
+    fn supports_ranges(&self) -> bool {
+        false
+    }
+
+    async fn extend(&self, object: &Address, epochs: u32) -> anyhow::Result<()>;
+
+    async fn delete(&self, object: &Address) -> anyhow::Result<()>;
+
+    /// Store several blobs, returning results in the same order as the inputs.
+    async fn put_many(
+        &self,
+        values: Vec<Bytes>,
+        epochs: u32,
+        deletable: bool,
+    ) -> anyhow::Result<Vec<NewBlob>> {
+        let mut blobs = Vec::with_capacity(values.len());
+        for value in values {
+            blobs.push(self.put(value, epochs, deletable).await?);
+        }
+        Ok(blobs)
+    }
+
+    /// Blob ID for an object created by this store, for backends whose
+    /// objects don't exist onchain.
+    fn blob_id_for_object(&self, _object: &Address) -> Option<String> {
+        None
+    }
+}
+
+#[async_trait]
+impl BlobStore for WalrusClient {
+    async fn put(&self, value: Bytes, epochs: u32, deletable: bool) -> anyhow::Result<NewBlob> {
+        self.write_blob(value, epochs, deletable).await
+    }
+
+    async fn get(&self, blob_id: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(self.read_blob(blob_id).await?.blob)
+    }
+
+    async fn get_range(&self, blob_id: &str, range: Range<u64>) -> anyhow::Result<Vec<u8>> {
+        self.read_blob_range(blob_id, range).await
+    }
+
+    fn supports_ranges(&self) -> bool {
+        true
+    }
+
+    async fn extend(&self, _object: &Address, _epochs: u32) -> anyhow::Result<()> {
+        Err(anyhow!(
+            "Walrus publishers can't extend blobs, use the Walrus CLI store"
+        ))
+    }
+
+    async fn delete(&self, _object: &Address) -> anyhow::Result<()> {
+        Err(anyhow!(
+            "Walrus publishers can't delete blobs, use the Walrus CLI store"
+        ))
+    }
+
+    async fn put_many(
+        &self,
+        values: Vec<Bytes>,
+        epochs: u32,
+        deletable: bool,
+    ) -> anyhow::Result<Vec<NewBlob>> {
+        self.write_blobs(values, epochs, deletable).await
+    }
+}
+
+/// Shells out to the Walrus CLI.
+pub struct WalrusCli;
+
+#[async_trait]
+impl BlobStore for WalrusCli {
+    async fn put(&self, value: Bytes, epochs: u32, deletable: bool) -> anyhow::Result<NewBlob> {
+        ffi::write_blobs(vec![&value], epochs, deletable)
+            .await?
+            .pop()
+            .ok_or(anyhow!("missing blob"))
+    }
+
+    async fn get(&self, blob_id: &str) -> anyhow::Result<Vec<u8>> {
+        Ok(ffi::read_blob(blob_id).await?.blob)
+    }
+
+    async fn extend(&self, object: &Address, epochs: u32) -> anyhow::Result<()> {
+        ffi::extend_blob(object, epochs).await
+    }
+
+    async fn delete(&self, object: &Address) -> anyhow::Result<()> {
+        ffi::delete_blob(object).await
+    }
+
+    async fn put_many(
+        &self,
+        values: Vec<Bytes>,
+        epochs: u32,
+        deletable: bool,
+    ) -> anyhow::Result<Vec<NewBlob>> {
+        ffi::write_blobs(values.iter().map(|v| &v[..]).collect(), epochs, deletable).await
+    }
+}
+
+/// Keeps blobs as files in a local directory, for offline and staging runs.
+pub struct DirStore {
+    dir: PathBuf,
+}
+
+impl DirStore {
+    pub fn new(dir: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).context("Failed to create blob directory")?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, blob_id: &str) -> PathBuf {
+        self.dir.join(blob_id)
+    }
+}
+
+#[async_trait]
+impl BlobStore for DirStore {
+    async fn put(&self, value: Bytes, _epochs: u32, _deletable: bool) -> anyhow::Result<NewBlob> {
+        let blob = local_blob(&value);
+        tokio::fs::write(self.path(&blob.blob_id), value)
+            .await
+            .context("Failed to write blob file")?;
+        Ok(blob)
+    }
+
+    async fn get(&self, blob_id: &str) -> anyhow::Result<Vec<u8>> {
+        tokio::fs::read(self.path(blob_id))
+            .await
+            .with_context(|| format!("Blob not found: {}", blob_id))
+    }
+
+    async fn get_range(&self, blob_id: &str, range: Range<u64>) -> anyhow::Result<Vec<u8>> {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+        let mut file = tokio::fs::File::open(self.path(blob_id))
+            .await
+            .with_context(|| format!("Blob not found: {}", blob_id))?;
+        if range.start > range.end || range.end > file.metadata().await?.len() {
+            return Err(anyhow!("Range {:?} is outside blob {}", range, blob_id));
+        }
+        file.seek(std::io::SeekFrom::Start(range.start)).await?;
+        let mut data = vec![0; (range.end - range.start) as usize];
+        file.read_exact(&mut data).await?;
+        Ok(data)
+    }
+
+    fn supports_ranges(&self) -> bool {
+        true
+    }
+
+    async fn extend(&self, object: &Address, _epochs: u32) -> anyhow::Result<()> {
+        // Local blobs never expire
+        let blob_id = local_blob_id(object);
+        if !tokio::fs::try_exists(self.path(&blob_id)).await? {
+            return Err(anyhow!("Blob not found: {}", blob_id));
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, object: &Address) -> anyhow::Result<()> {
+        let blob_id = local_blob_id(object);
+        tokio::fs::remove_file(self.path(&blob_id))
+            .await
+            .with_context(|| format!("Blob not found: {}", blob_id))
+    }
+
+    fn blob_id_for_object(&self, object: &Address) -> Option<String> {
+        Some(local_blob_id(object))
+    }
+}
+
+/// Keeps blobs in memory, for tests.
+#[derive(Default)]
+pub struct MemoryStore {
+    blobs: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl BlobStore for MemoryStore {
+    async fn put(&self, value: Bytes, _epochs: u32, _deletable: bool) -> anyhow::Result<NewBlob> {
+        let blob = local_blob(&value);
+        self.blobs
+            .lock()
+            .unwrap()
+            .insert(blob.blob_id.clone(), value.to_vec());
+        Ok(blob)
+    }
+
+    async fn get(&self, blob_id: &str) -> anyhow::Result<Vec<u8>> {
+        self.blobs
+            .lock()
+            .unwrap()
+            .get(blob_id)
+            .cloned()
+            .ok_or(anyhow!("Blob not found: {}", blob_id))
+    }
+
+    async fn get_range(&self, blob_id: &str, range: Range<u64>) -> anyhow::Result<Vec<u8>> {
+        let blobs = self.blobs.lock().unwrap();
+        let data = blobs
+            .get(blob_id)
+            .ok_or(anyhow!("Blob not found: {}", blob_id))?;
+        slice_range(data, range)
+    }
+
+    fn supports_ranges(&self) -> bool {
+        true
+    }
+
+    async fn extend(&self, object: &Address, _epochs: u32) -> anyhow::Result<()> {
+        let blob_id = local_blob_id(object);
+        if !self.blobs.lock().unwrap().contains_key(&blob_id) {
+            return Err(anyhow!("Blob not found: {}", blob_id));
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, object: &Address) -> anyhow::Result<()> {
+        let blob_id = local_blob_id(object);
+        self.blobs
+            .lock()
+            .unwrap()
+            .remove(&blob_id)
+            .map(|_| ())
+            .ok_or(anyhow!("Blob not found: {}", blob_id))
+    }
+
+    fn blob_id_for_object(&self, object: &Address) -> Option<String> {
+        Some(local_blob_id(object))
+    }
+}
+
+pub(crate) fn slice_range(data: &[u8], range: Range<u64>) -> anyhow::Result<Vec<u8>> {
+    let start = usize::try_from(range.start)?;
+    let end = usize::try_from(range.end)?;
+    data.get(start..end)
+        .map(<[u8]>::to_vec)
+        .ok_or(anyhow!("Range {:?} is outside the blob", range))
+}
+
+// Local blobs are content addressed, and the object address is the same
+// 32 bytes so a blob ID can be recovered from the address alone.
+fn local_blob(value: &[u8]) -> NewBlob {
+    let hash: [u8; 32] = Blake2b::digest(value).into();
+    let object_address = Address::new(hash);
+    NewBlob {
+        blob_id: local_blob_id(&object_address),
+        object_address,
+    }
+}
+
+fn local_blob_id(object: &Address) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(object.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        merkle::{MerkleTree, TreeVersion},
+        wallets, AllocationExt,
+    };
+
+    fn campaign() -> Vec<(Address, u64)> {
+        (1..=10u8)
+            .map(|i| (Address::new([i; 32]), i as u64 * 1_000))
+            .collect()
+    }
+
+    async fn roundtrip(store: &dyn BlobStore) {
+        let (_, wallets) = wallets::clean_addresses(campaign()).unwrap();
+        let leaves: Vec<_> = wallets
+            .iter()
+            .map(|(addr, allo)| wallets::hash_allo(addr, *allo))
+            .collect();
+        let tree = MerkleTree::new(&leaves).unwrap();
+
+        let data = crate::CampaignData::encode(&tree, &wallets).unwrap();
+        let blobs = crate::write_campaign_blobs(store, &data, 4, true)
+            .await
+            .unwrap();
+        let tree_blob = blobs.merkle_tree.unwrap();
+
+        let fetched_tree = crate::fetch_merkle_tree_blob(store, &tree_blob.blob_id)
+            .await
+            .unwrap();
+        let fetched_allos = crate::fetch_allocations_blob(store, &blobs.allocations.blob_id)
+            .await
+            .unwrap();
+        assert_eq!(fetched_tree.get_root(), tree.get_root());
+        assert_eq!(fetched_allos, wallets);
+
+        let leaf_index = fetched_allos.get_position(&Address::new([6; 32])).unwrap();
+        let leaf_proof = crate::fetch_proof_blob(store, &tree_blob.blob_id, leaf_index as u64)
+            .await
+            .unwrap();
+        assert_eq!(leaf_proof, tree.leaf_proof(leaf_index as u64).unwrap());
+
+        let wallet = Address::new([7; 32]);
+        let leaf = fetched_allos
+            .get_leaf(&wallet, fetched_tree.version)
+            .unwrap();
+        let (_, proof) = fetched_tree.get_proof(&leaf);
+        assert!(fetched_tree.verify_proof(&leaf, &proof));
+
+        // Claims from proofs read one at a time match the whole tree's
+        let drop = crate::txns::Drop {
+            id: [0; 32],
+            root: tree.get_root().to_vec(),
+            wallet_count: tree.leaf_count,
+            airdrop_total: 0,
+            vault: 0,
+            allocations: blobs.allocations.object_address,
+            merkle_tree: tree_blob.object_address,
+            registry: crate::txns::Table {
+                id: [0; 32],
+                size: 0,
+            },
+            tree_version: TreeVersion::V1,
+        };
+        let batch = [6, 2, 3, 9].map(|i| Address::new([i; 32]));
+        let whole = crate::CampaignTree::Whole(tree.clone())
+            .multi_claim(store, &drop, &fetched_allos, &batch)
+            .await
+            .unwrap();
+        let manifest = crate::read_manifest(store, &tree_blob.blob_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let sharded = crate::CampaignTree::Sharded {
+            blob_id: tree_blob.blob_id.clone(),
+            manifest,
+        };
+        let claim = sharded
+            .multi_claim(store, &drop, &fetched_allos, &batch)
+            .await
+            .unwrap();
+        assert_eq!(claim.wallets, whole.wallets);
+        assert_eq!(claim.proof, whole.proof);
+
+        let object = tree_blob.object_address;
+        assert_eq!(
+            store.blob_id_for_object(&object),
+            Some(tree_blob.blob_id.clone())
+        );
+        store.extend(&object, 4).await.unwrap();
+        store.delete(&object).await.unwrap();
+        assert!(store.get(&tree_blob.blob_id).await.is_err());
+        assert!(store.extend(&object, 4).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        roundtrip(&MemoryStore::new()).await;
+    }
+
+    #[tokio::test]
+    async fn test_csv_allocations() {
+        // Campaigns created before the binary format still load
+        let store = MemoryStore::new();
+        let (_, wallets) = wallets::clean_addresses(campaign()).unwrap();
+        let csv = wallets::write_wallets_to_bytes(&wallets).unwrap();
+        let blob = store.put(csv.into(), 4, true).await.unwrap();
+        let fetched = crate::fetch_allocations_blob(&store, &blob.blob_id)
+            .await
+            .unwrap();
+        assert_eq!(fetched, wallets);
+
+        // Their order was never checked. Rows stay in leaf order, and
+        // lookups still find every wallet
+        let reversed: Vec<_> = wallets.iter().rev().copied().collect();
+        let csv = wallets::write_wallets_to_bytes(&reversed).unwrap();
+        let blob = store.put(csv.into(), 4, true).await.unwrap();
+        let fetched = crate::fetch_allocations_blob(&store, &blob.blob_id)
+            .await
+            .unwrap();
+        assert_eq!(fetched, reversed);
+        for (i, (addr, allo)) in reversed.iter().enumerate() {
+            assert_eq!(fetched.get_position(addr), Some(i));
+            assert_eq!(fetched.get_allocation(addr), Some(*allo));
+        }
+        assert_eq!(fetched.get_position(&Address::new([0xee; 32])), None);
+    }
+
+    #[tokio::test]
+    async fn test_bcs_tree() {
+        // Campaigns created before the sharded format still load
+        let store = MemoryStore::new();
+        let (_, wallets) = wallets::clean_addresses(campaign()).unwrap();
+        let leaves: Vec<_> = wallets
+            .iter()
+            .map(|(addr, allo)| wallets::hash_allo(addr, *allo))
+            .collect();
+        let tree = MerkleTree::new(&leaves).unwrap();
+        let blob = store
+            .put(bcs::to_bytes(&tree).unwrap().into(), 4, true)
+            .await
+            .unwrap();
+
+        let fetched = crate::fetch_merkle_tree_blob(&store, &blob.blob_id)
+            .await
+            .unwrap();
+        assert_eq!(fetched, tree);
+        let leaf_proof = crate::fetch_proof_blob(&store, &blob.blob_id, 3)
+            .await
+            .unwrap();
+        assert_eq!(leaf_proof, tree.leaf_proof(3).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_compressed_campaign() {
+        let store = MemoryStore::new();
+        let (_, wallets) = wallets::clean_addresses(campaign()).unwrap();
+        let leaves: Vec<_> = wallets
+            .iter()
+            .map(|(addr, allo)| wallets::hash_allo(addr, *allo))
+            .collect();
+        let tree = MerkleTree::new(&leaves).unwrap();
+        let data = crate::CampaignData::encode(&tree, &wallets)
+            .unwrap()
+            .compress(crate::compress::DEFAULT_LEVEL)
+            .unwrap();
+        assert!(crate::compress::is_compressed(&data.allocations));
+
+        let blobs = crate::write_campaign_blobs(&store, &data, 4, true)
+            .await
+            .unwrap();
+        let fetched_tree =
+            crate::fetch_merkle_tree_blob(&store, &blobs.merkle_tree.unwrap().blob_id)
+                .await
+                .unwrap();
+        let fetched_allos = crate::fetch_allocations_blob(&store, &blobs.allocations.blob_id)
+            .await
+            .unwrap();
+        assert_eq!(fetched_tree, tree);
+        assert_eq!(fetched_allos, wallets);
+    }
+
+    #[tokio::test]
+    async fn test_allocations_only_campaign() {
+        let store = MemoryStore::new();
+        let (_, wallets) = wallets::clean_addresses(campaign()).unwrap();
+        let tree = wallets::build_tree(&wallets, TreeVersion::V1).unwrap();
+        let data = crate::CampaignData::encode(&tree, &wallets)
+            .unwrap()
+            .without_tree();
+        assert_eq!(data.sizes()[0], 0);
+
+        let blobs = crate::write_campaign_blobs(&store, &data, 4, true)
+            .await
+            .unwrap();
+        assert!(blobs.merkle_tree.is_none());
+        assert_eq!(blobs.tree_object(), Address::ZERO);
+        assert_eq!(store.blobs.lock().unwrap().len(), 1);
+
+        let fetched = crate::fetch_allocations_blob(&store, &blobs.allocations.blob_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            wallets::build_tree(&fetched, TreeVersion::V1).unwrap(),
+            tree
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dir_store() {
+        let dir = tempfile::tempdir().unwrap();
+        roundtrip(&DirStore::new(dir.path()).unwrap()).await;
+    }
+}
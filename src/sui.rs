@@ -3,13 +3,30 @@ use std::str::FromStr;
 use sui_sdk_types::{Address, ObjectId, TypeTag};
 use sui_transaction_builder::{unresolved::Input, TransactionBuilder};
 
+/// Gas budget and price for built transactions, in MIST.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GasConfig {
+    pub budget: u64,
+    pub price: u64,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            budget: 6_000_000,
+            price: 1_000,
+        }
+    }
+}
+
 pub async fn create_tx(
     client: &sui_graphql_client::Client,
     sender: &Address,
+    gas_config: &GasConfig,
 ) -> anyhow::Result<TransactionBuilder> {
     let mut builder = TransactionBuilder::new();
 
-    let budget = 6_000_000;
+    let budget = gas_config.budget;
 
     let sui_coin = crate::sui_coin().to_string();
     let gas_objs = client
@@ -36,11 +53,33 @@ pub async fn create_tx(
     builder.add_gas_objects(vec![owned_obj]);
 
     builder.set_gas_budget(budget);
-    builder.set_gas_price(1_000);
+    builder.set_gas_price(gas_config.price);
 
     Ok(builder)
 }
 
+/// What's needed to display amounts of a coin.
+pub struct CoinInfo {
+    pub decimals: u8,
+    pub symbol: String,
+}
+
+pub async fn fetch_coin_info(
+    client: &sui_graphql_client::Client,
+    coin_type: &TypeTag,
+) -> anyhow::Result<CoinInfo> {
+    let coin = client
+        .coin_metadata(&coin_type.to_string())
+        .await?
+        .ok_or(anyhow!("coin not found: {}", coin_type))?;
+    let decimals = coin.decimals.ok_or(anyhow!("unknown decimals"))?;
+
+    Ok(CoinInfo {
+        decimals: u8::try_from(decimals)?,
+        symbol: coin.symbol.unwrap_or("TOKEN".to_string()),
+    })
+}
+
 pub async fn fetch_bcs<T: serde::de::DeserializeOwned>(
     client: &sui_graphql_client::Client,
     id: &ObjectId,
@@ -125,6 +164,59 @@ pub fn find_created_shared_obj(tx: &sui_sdk_types::TransactionEffects) -> anyhow
     Err(anyhow!("obj not found"))
 }
 
+/// An object the transaction created and sent to `owner`.
+pub fn find_created_owned_obj(
+    tx: &sui_sdk_types::TransactionEffects,
+    owner: &Address,
+) -> anyhow::Result<ObjectId> {
+    if let sui_sdk_types::TransactionEffects::V2(data) = tx {
+        let obj = data.changed_objects.iter().find(|x| {
+            let is_owned = matches!(
+                x.output_state,
+                sui_sdk_types::ObjectOut::ObjectWrite {
+                    owner: sui_sdk_types::Owner::Address(addr),
+                    ..
+                } if addr == *owner
+            );
+            is_owned && x.id_operation == sui_sdk_types::IdOperation::Created
+        });
+        if let Some(val) = obj {
+            return Ok(val.object_id);
+        }
+    }
+    Err(anyhow!("obj not found"))
+}
+
+/// The current Walrus storage epoch, read from the Walrus `System` object.
+pub async fn fetch_walrus_epoch(
+    client: &sui_graphql_client::Client,
+    system: &ObjectId,
+) -> anyhow::Result<u32> {
+    let obj = client
+        .move_object_contents((*system).into(), None)
+        .await?
+        .ok_or(anyhow!("object not found"))?;
+    let version = json_u64(obj.get("version")).context("malformed system object")?;
+
+    // The system state lives in a dynamic field keyed by its version
+    let inner = client
+        .dynamic_field((*system).into(), TypeTag::U64, version)
+        .await?
+        .and_then(|field| field.value_as_json)
+        .ok_or(anyhow!("system state not found"))?;
+    let epoch = json_u64(inner.pointer("/committee/epoch")).context("malformed system state")?;
+    Ok(u32::try_from(epoch)?)
+}
+
+// Move u64s come back from GraphQL as strings, smaller ints as numbers
+fn json_u64(value: Option<&serde_json::Value>) -> anyhow::Result<u64> {
+    let value = value.ok_or(anyhow!("missing field"))?;
+    value
+        .as_u64()
+        .or_else(|| value.as_str()?.parse().ok())
+        .ok_or(anyhow!("not a number: {}", value))
+}
+
 pub async fn get_blob_from_obj(
     client: &sui_graphql_client::Client,
     id: &Address,
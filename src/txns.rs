@@ -1,9 +1,11 @@
 use crate::{
-    merkle, package_id,
-    sui::{create_tx, get_owned_obj, get_shared_obj, parse_address},
+    claim::MultiClaim,
+    merkle::{self, TreeVersion},
+    network::NetworkConfig,
+    sui::{create_tx, fetch_bcs, get_owned_obj, get_shared_obj, parse_address},
 };
 use anyhow::anyhow;
-use sui_sdk_types::{Address, Identifier, ObjectId, Transaction, TypeTag};
+use sui_sdk_types::{Address, Identifier, ObjectId, Transaction, TransactionDigest, TypeTag};
 use sui_transaction_builder::Serialized;
 
 #[derive(serde::Deserialize, Debug, serde::Serialize)]
@@ -30,10 +32,114 @@ pub struct Drop {
     #[serde(deserialize_with = "parse_address")]
     pub merkle_tree: Address,
     pub registry: Table,
+    /// Kept in a dynamic field rather than the struct, see `fetch_drop`.
+    #[serde(skip)]
+    pub tree_version: TreeVersion,
 }
 
+/// Name of the `Drop` dynamic field holding its tree version. Campaigns
+/// without it are v1.
+pub const TREE_VERSION_FIELD: &[u8] = b"tree_version";
+
+/// A `drop::Drop` dynamic field with a `vector<u8>` name.
+#[derive(serde::Deserialize)]
+struct Field<T> {
+    _id: [u8; 32],
+    _name: Vec<u8>,
+    value: T,
+}
+
+/// Read a `Drop` along with its tree version.
+pub async fn fetch_drop(
+    client: &sui_graphql_client::Client,
+    drop_id: &ObjectId,
+) -> anyhow::Result<Drop> {
+    let mut drop: Drop = fetch_bcs(client, drop_id).await?;
+    let field_id = drop_id.derive_dynamic_child_id(
+        &TypeTag::Vector(Box::new(TypeTag::U8)),
+        &bcs::to_bytes(TREE_VERSION_FIELD)?,
+    );
+    drop.tree_version = match client
+        .move_object_contents_bcs(field_id.into(), None)
+        .await?
+    {
+        Some(data) => bcs::from_bytes::<Field<u8>>(&data)?.value.try_into()?,
+        None => TreeVersion::V1,
+    };
+    Ok(drop)
+}
+
+impl Drop {
+    /// The tree blob object, `None` for campaigns that only store the
+    /// allocations and have `@0x0` in its place.
+    pub fn tree_blob(&self) -> Option<Address> {
+        Some(self.merkle_tree).filter(|object| *object != Address::ZERO)
+    }
+}
+
+/// Whether a wallet has claimed from a drop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum ClaimStatus {
+    Claimed { tx_digest: TransactionDigest },
+    Unclaimed,
+    NotEligible,
+}
+
+impl ClaimStatus {
+    pub fn new(claim_tx: Option<TransactionDigest>, allocation: Option<u64>) -> Self {
+        match (claim_tx, allocation) {
+            (Some(tx_digest), _) => Self::Claimed { tx_digest },
+            (None, Some(_)) => Self::Unclaimed,
+            (None, None) => Self::NotEligible,
+        }
+    }
+
+    /// The serialized name of the status, e.g. for a CSV column.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Claimed { .. } => "claimed",
+            Self::Unclaimed => "unclaimed",
+            Self::NotEligible => "not_eligible",
+        }
+    }
+
+    pub fn tx_digest(&self) -> Option<TransactionDigest> {
+        match self {
+            Self::Claimed { tx_digest } => Some(*tx_digest),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ClaimStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Claimed { tx_digest } => write!(f, "claimed (tx {})", tx_digest),
+            Self::Unclaimed => f.write_str("unclaimed"),
+            Self::NotEligible => f.write_str("not eligible"),
+        }
+    }
+}
+
+/// The transaction that claimed for `wallet`, found through the wallet's
+/// entry in the drop's registry table. `None` if it hasn't claimed.
+pub async fn find_claim(
+    client: &sui_graphql_client::Client,
+    drop: &Drop,
+    wallet: &Address,
+) -> anyhow::Result<Option<TransactionDigest>> {
+    let table = ObjectId::from(drop.registry.id);
+    let field_id = table.derive_dynamic_child_id(&TypeTag::Address, &bcs::to_bytes(wallet)?);
+    // Entries are only ever added, so the field was last written by the claim
+    let field = client.object(field_id.into(), None).await?;
+    Ok(field.map(|obj| obj.previous_transaction()))
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn create_claim_tx(
     client: &sui_graphql_client::Client,
+    net: &NetworkConfig,
     sender: &Address,
     proof: &merkle::Proof,
     leaf_index: u64,
@@ -41,10 +147,10 @@ pub async fn create_claim_tx(
     coin_type: &TypeTag,
     allo: u64,
 ) -> anyhow::Result<Transaction> {
-    let mut builder = create_tx(client, sender).await?;
+    let mut builder = create_tx(client, sender, &net.gas).await?;
 
     let func = sui_transaction_builder::Function::new(
-        package_id(),
+        net.call_package_id()?,
         Identifier::new("drop")?,
         Identifier::new("claim")?,
         vec![coin_type.clone()],
@@ -68,8 +174,65 @@ pub async fn create_claim_tx(
     Ok(tx)
 }
 
+/// Fails unless the call package has `drop::claim_many`, which the first
+/// published packages predate.
+pub async fn check_claim_many(
+    client: &sui_graphql_client::Client,
+    net: &NetworkConfig,
+) -> anyhow::Result<()> {
+    let package = net.call_package_id()?;
+    let function = client
+        .normalized_move_function(&package.to_string(), "drop", "claim_many", None)
+        .await?;
+    if function.is_none() {
+        return Err(anyhow!(
+            "Package {} has no drop::claim_many, set latest_package_id to an upgraded package",
+            package
+        ));
+    }
+    Ok(())
+}
+
+/// A `drop::claim_many` call, which sends each wallet its allocation.
+pub async fn create_claim_many_tx(
+    client: &sui_graphql_client::Client,
+    net: &NetworkConfig,
+    sender: &Address,
+    claim: &MultiClaim,
+    drop_id: &ObjectId,
+    coin_type: &TypeTag,
+) -> anyhow::Result<Transaction> {
+    let mut builder = create_tx(client, sender, &net.gas).await?;
+
+    let func = sui_transaction_builder::Function::new(
+        net.call_package_id()?,
+        Identifier::new("drop")?,
+        Identifier::new("claim_many")?,
+        vec![coin_type.clone()],
+    );
+
+    let wallets: Vec<_> = claim.wallets.iter().map(|(wallet, _)| *wallet).collect();
+    let allocations: Vec<_> = claim.wallets.iter().map(|(_, allo)| *allo).collect();
+    let proof_data: Vec<_> = claim.proof.nodes.iter().map(|x| x.to_vec()).collect();
+
+    let drop_obj = get_shared_obj(client, drop_id, true).await?;
+
+    let arg0 = builder.input(Serialized(&wallets));
+    let arg1 = builder.input(Serialized(&allocations));
+    let arg2 = builder.input(Serialized(&claim.proof.leaf_indices));
+    let arg3 = builder.input(Serialized(&proof_data));
+    let arg4 = builder.input(drop_obj);
+    builder.move_call(func, vec![arg0, arg1, arg2, arg3, arg4]);
+
+    let tx = builder.finish()?;
+
+    Ok(tx)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn create_drop_tx(
     client: &sui_graphql_client::Client,
+    net: &NetworkConfig,
     sender: &Address,
     walrus_addresses: &Address,
     walrus_merkle: &Address,
@@ -77,13 +240,20 @@ pub async fn create_drop_tx(
     wallet_count: u32,
     coin_type: &TypeTag,
     merkle_root: &merkle::Hash,
+    tree_version: TreeVersion,
 ) -> anyhow::Result<Transaction> {
-    let mut builder = create_tx(client, sender).await?;
+    let mut builder = create_tx(client, sender, &net.gas).await?;
 
+    // v1 campaigns keep using the original entry point, so they can be
+    // created with packages from before v2 trees
+    let create = match tree_version {
+        TreeVersion::V1 => "create_drop",
+        TreeVersion::V2 => "create_drop_v2",
+    };
     let func = sui_transaction_builder::Function::new(
-        package_id(),
+        net.call_package_id()?,
         Identifier::new("drop")?,
-        Identifier::new("create_drop")?,
+        Identifier::new(create)?,
         vec![coin_type.clone()],
     );
 
@@ -128,15 +298,16 @@ pub async fn create_drop_tx(
 
 pub async fn delete_drop_tx(
     client: &sui_graphql_client::Client,
+    net: &NetworkConfig,
     sender: &Address,
     coin_type: &TypeTag,
     drop_id: &ObjectId,
     cap_id: &ObjectId,
 ) -> anyhow::Result<Transaction> {
-    let mut builder = create_tx(client, sender).await?;
+    let mut builder = create_tx(client, sender, &net.gas).await?;
 
     let func = sui_transaction_builder::Function::new(
-        package_id(),
+        net.call_package_id()?,
         Identifier::new("drop")?,
         Identifier::new("destroy_drop")?,
         vec![coin_type.clone()],
@@ -156,36 +327,58 @@ pub async fn delete_drop_tx(
     Ok(tx)
 }
 
-pub async fn get_delete_cap(
+/// Every `DeleteCap` owned by `owner`, across all pages.
+pub async fn list_delete_caps(
     client: &sui_graphql_client::Client,
-    sender: &Address,
-    drop_id: &ObjectId,
-) -> anyhow::Result<ObjectId> {
-    let delete_cap_type = format!("{}::drop::DeleteCap", package_id());
-    let delete_caps = client
-        .objects(
-            Some(sui_graphql_client::query_types::ObjectFilter {
-                owner: Some(*sender),
-                object_ids: None,
-                type_: Some(&delete_cap_type),
-            }),
-            sui_graphql_client::PaginationFilter {
-                direction: sui_graphql_client::Direction::Forward,
-                cursor: None,
-                limit: Some(10),
-            },
-        )
-        .await?;
+    net: &NetworkConfig,
+    owner: &Address,
+) -> anyhow::Result<Vec<DeleteCap>> {
+    let delete_cap_type = format!("{}::drop::DeleteCap", net.package_id()?);
+    let mut caps = Vec::new();
+    let mut cursor = None;
 
-    for obj in delete_caps.data() {
-        if let sui_sdk_types::ObjectData::Struct(data) = obj.data() {
-            let gg: DeleteCap = bcs::from_bytes(data.contents())?;
-            let id = ObjectId::from(gg.object_id);
-            if *drop_id == id {
-                return Ok(ObjectId::from(gg.id));
+    loop {
+        let page = client
+            .objects(
+                Some(sui_graphql_client::query_types::ObjectFilter {
+                    owner: Some(*owner),
+                    object_ids: None,
+                    type_: Some(&delete_cap_type),
+                }),
+                sui_graphql_client::PaginationFilter {
+                    direction: sui_graphql_client::Direction::Forward,
+                    cursor: cursor.clone(),
+                    limit: Some(50),
+                },
+            )
+            .await?;
+
+        for obj in page.data() {
+            if let sui_sdk_types::ObjectData::Struct(data) = obj.data() {
+                caps.push(bcs::from_bytes(data.contents())?);
             }
         }
+
+        let info = page.page_info();
+        if !info.has_next_page {
+            break;
+        }
+        cursor = info.end_cursor.clone();
     }
 
-    Err(anyhow!("DeleteCap not found"))
+    Ok(caps)
+}
+
+/// The `DeleteCap` for `drop_id`, if `sender` owns it.
+pub async fn get_delete_cap(
+    client: &sui_graphql_client::Client,
+    net: &NetworkConfig,
+    sender: &Address,
+    drop_id: &ObjectId,
+) -> anyhow::Result<Option<ObjectId>> {
+    Ok(list_delete_caps(client, net, sender)
+        .await?
+        .into_iter()
+        .find(|cap| ObjectId::from(cap.object_id) == *drop_id)
+        .map(|cap| ObjectId::from(cap.id)))
 }
@@ -1,4 +1,5 @@
-use crate::merkle::Hash;
+use crate::builder::TreeBuilder;
+use crate::merkle::{self, Hash, MerkleTree, TreeVersion};
 use anyhow::{anyhow, Context};
 use blake2::Digest;
 use csv::ReaderBuilder;
@@ -7,11 +8,16 @@ use std::fs::File;
 use sui_sdk_types::Address;
 
 pub fn parse_csv<R: std::io::Read>(reader: R) -> anyhow::Result<Vec<(Address, u64)>> {
-    let mut rdr = ReaderBuilder::new().has_headers(false).from_reader(reader);
+    csv_rows(reader).collect()
+}
 
-    let mut results = Vec::new();
+/// Rows of an allocations CSV, parsed as they're read.
+pub fn csv_rows<R: std::io::Read>(
+    reader: R,
+) -> impl Iterator<Item = anyhow::Result<(Address, u64)>> {
+    let rdr = ReaderBuilder::new().has_headers(false).from_reader(reader);
 
-    for result in rdr.records() {
+    rdr.into_records().map(|result| {
         let record = result.context("Failed to parse CSV record")?;
 
         // Ensure the record has exactly 2 fields
@@ -22,17 +28,39 @@ pub fn parse_csv<R: std::io::Read>(reader: R) -> anyhow::Result<Vec<(Address, u6
         let address = record[0].parse()?;
         let balance = record[1].parse()?;
 
-        results.push((address, balance));
-    }
-
-    Ok(results)
+        Ok((address, balance))
+    })
 }
 
-pub fn read_wallets_csv(path: &str) -> anyhow::Result<Vec<(Address, u64)>> {
+pub fn read_wallets_csv(path: impl AsRef<std::path::Path>) -> anyhow::Result<Vec<(Address, u64)>> {
     let file = File::open(path).context("Failed to open CSV file")?;
     parse_csv(file)
 }
 
+/// A list of wallets to look up, one address per line. Blank lines and `#`
+/// comments are skipped, and only the first column of a CSV row is read, so
+/// an allocations CSV works too.
+pub fn parse_address_list(text: &str) -> anyhow::Result<Vec<Address>> {
+    let mut addresses = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let field = line.split(',').next().unwrap_or_default().trim();
+        let address = field
+            .parse()
+            .map_err(|_| anyhow!("Invalid address on line {}: {}", i + 1, field))?;
+        addresses.push(address);
+    }
+    Ok(addresses)
+}
+
+pub fn read_address_list(path: impl AsRef<std::path::Path>) -> anyhow::Result<Vec<Address>> {
+    let text = std::fs::read_to_string(path).context("Failed to open address list")?;
+    parse_address_list(&text)
+}
+
 pub fn parse_csv_bytes(data: &[u8]) -> anyhow::Result<Vec<(Address, u64)>> {
     let cursor = std::io::Cursor::new(data);
     parse_csv(cursor)
@@ -82,8 +110,75 @@ pub fn clean_addresses(
 }
 
 pub fn hash_allo(address: &Address, allo: u64) -> Hash {
+    hash_leaf(TreeVersion::V1, address, allo)
+}
+
+/// The leaf of a wallet's allocation, prefixed with `merkle::LEAF_TAG` in v2
+/// trees.
+pub fn hash_leaf(version: TreeVersion, address: &Address, allo: u64) -> Hash {
     let mut hasher = blake2::Blake2b::new();
+    if version == TreeVersion::V2 {
+        hasher.update([merkle::LEAF_TAG]);
+    }
     hasher.update(bcs::to_bytes(address).expect("u64 address fail"));
     hasher.update(bcs::to_bytes(&allo).expect("u64 bcs fail"));
     hasher.finalize().into()
 }
+
+/// The campaign tree of a cleaned allocations list, one leaf per row, built
+/// on every core.
+pub fn build_tree(
+    allocations: &[(Address, u64)],
+    version: TreeVersion,
+) -> anyhow::Result<MerkleTree> {
+    TreeBuilder::new()
+        .version(version)
+        .build(allocations.len(), allocations.iter().copied())?
+        .into_tree()
+}
+
+/// A raw token amount in whole tokens, e.g. 1_500_000 with 6 decimals is "1.5".
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    // Too many decimals for the shift to fit, show the raw amount
+    let Some(shift) = 10u128.checked_pow(decimals as u32) else {
+        return amount.to_string();
+    };
+    let whole = amount as u128 / shift;
+    let frac = amount as u128 % shift;
+    if frac == 0 {
+        return whole.to_string();
+    }
+    let frac = format!("{:0width$}", frac, width = decimals as usize);
+    format!("{}.{}", whole, frac.trim_end_matches('0'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(1_500_000, 6), "1.5");
+        assert_eq!(format_amount(1_000_000_000, 9), "1");
+        assert_eq!(format_amount(1, 9), "0.000000001");
+        assert_eq!(format_amount(42, 0), "42");
+        assert_eq!(format_amount(u64::MAX, 19), "1.8446744073709551615");
+        assert_eq!(
+            format_amount(1_500_000, 38),
+            "0.000000000000000000000000000000015"
+        );
+        assert_eq!(format_amount(1_500_000, 39), "1500000");
+        assert_eq!(format_amount(7, u8::MAX), "7");
+    }
+
+    #[test]
+    fn test_parse_address_list() {
+        let a = Address::new([1; 32]);
+        let b = Address::new([2; 32]);
+        let text = format!("# tickets\n{}\n\n  {}, 500\n", a, b);
+        assert_eq!(parse_address_list(&text).unwrap(), vec![a, b]);
+
+        let err = parse_address_list("0x1\nnot-an-address\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+    }
+}
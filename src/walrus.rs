@@ -0,0 +1,424 @@
+use crate::ffi::{Blob, NewBlob};
+use anyhow::{anyhow, Context};
+use base64::Engine;
+use bytes::Bytes;
+use std::ops::Range;
+use sui_sdk_types::Address;
+use url::Url;
+
+pub const TESTNET_PUBLISHER: &str = "https://publisher.walrus-testnet.walrus.space";
+pub const TESTNET_AGGREGATOR: &str = "https://aggregator.walrus-testnet.walrus.space";
+pub const MAINNET_AGGREGATOR: &str = "https://aggregator.walrus-mainnet.walrus.space";
+
+/// Mirrors `walrus::blob::Blob`, the Sui object created for every stored blob.
+#[derive(Debug, serde::Deserialize)]
+pub struct BlobObject {
+    pub id: Address,
+    pub registered_epoch: u32,
+    /// u256, little endian.
+    pub blob_id: [u8; 32],
+    pub size: u64,
+    pub encoding_type: u8,
+    pub certified_epoch: Option<u32>,
+    pub storage: StorageResource,
+    pub deletable: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct StorageResource {
+    pub id: Address,
+    pub start_epoch: u32,
+    pub end_epoch: u32,
+    pub storage_size: u64,
+}
+
+impl BlobObject {
+    /// The blob ID as used by publishers and aggregators.
+    pub fn blob_id(&self) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(self.blob_id)
+    }
+}
+
+const MIB: u64 = 1024 * 1024;
+
+// Every blob carries roughly this much metadata once encoded, whatever its size
+const METADATA_OVERHEAD: u64 = 64 * MIB;
+
+/// Approximate size of a blob once erasure coded: about 5x the input, plus
+/// the fixed metadata overhead.
+pub fn encoded_size(len: u64) -> u64 {
+    len * 5 + METADATA_OVERHEAD
+}
+
+/// Walrus storage prices in FROST (10^9 FROST = 1 WAL). The defaults are
+/// ballpark figures, `walrus info` shows the current ones.
+#[derive(Clone, Copy, Debug)]
+pub struct StoragePrice {
+    pub storage_per_mib_epoch: u64,
+    pub write_per_mib: u64,
+}
+
+impl Default for StoragePrice {
+    fn default() -> Self {
+        Self {
+            storage_per_mib_epoch: 100_000,
+            write_per_mib: 20_000,
+        }
+    }
+}
+
+impl StoragePrice {
+    /// Estimated cost in FROST of storing blobs of these sizes for `epochs`.
+    pub fn estimate(&self, sizes: &[u64], epochs: u32) -> u64 {
+        sizes
+            .iter()
+            .map(|len| {
+                let units = encoded_size(*len).div_ceil(MIB);
+                units * (self.storage_per_mib_epoch * epochs as u64 + self.write_per_mib)
+            })
+            .sum()
+    }
+}
+
+/// Talks to Walrus publisher and aggregator HTTP endpoints directly, so no
+/// Walrus CLI is needed.
+#[derive(Clone, Debug)]
+pub struct WalrusClient {
+    http: reqwest::Client,
+    publisher: Option<Url>,
+    aggregator: Url,
+    send_object_to: Option<Address>,
+}
+
+impl WalrusClient {
+    pub fn new(publisher: Url, aggregator: Url) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            publisher: Some(publisher),
+            aggregator,
+            send_object_to: None,
+        }
+    }
+
+    /// A client that can only read blobs, for networks without a public publisher.
+    pub fn read_only(aggregator: Url) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            publisher: None,
+            aggregator,
+            send_object_to: None,
+        }
+    }
+
+    pub fn testnet() -> Self {
+        Self::new(
+            Url::parse(TESTNET_PUBLISHER).expect("bad publisher url"),
+            Url::parse(TESTNET_AGGREGATOR).expect("bad aggregator url"),
+        )
+    }
+
+    /// Have the publisher transfer newly created blob objects to this address.
+    pub fn send_object_to(mut self, owner: Address) -> Self {
+        self.send_object_to = Some(owner);
+        self
+    }
+
+    pub async fn write_blob(
+        &self,
+        value: Bytes,
+        epochs: u32,
+        deletable: bool,
+    ) -> anyhow::Result<NewBlob> {
+        let publisher = self.publisher.as_ref().ok_or(anyhow!(
+            "No Walrus publisher configured, use the Walrus CLI store or set a publisher URL"
+        ))?;
+        let mut url = publisher.join("v1/blobs")?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("epochs", &epochs.to_string());
+            if deletable {
+                query.append_pair("deletable", "true");
+            }
+            if let Some(owner) = &self.send_object_to {
+                query.append_pair("send_object_to", &owner.to_string());
+            }
+        }
+
+        let response = self
+            .http
+            .put(url)
+            .body(value)
+            .send()
+            .await
+            .context("Failed to reach Walrus publisher")?;
+        let response = check_status(response).await?;
+
+        let res: StoreResponse = response.json().await?;
+
+        let created = res.newly_created.ok_or_else(|| {
+            anyhow!("Walrus publisher did not create a new blob object (already certified)")
+        })?;
+
+        Ok(NewBlob {
+            blob_id: created.blob_object.blob_id,
+            object_address: created.blob_object.id.parse()?,
+        })
+    }
+
+    pub async fn write_blobs(
+        &self,
+        values: Vec<Bytes>,
+        epochs: u32,
+        deletable: bool,
+    ) -> anyhow::Result<Vec<NewBlob>> {
+        // Results are returned in the same order as the inputs
+        let blobs = futures::future::try_join_all(
+            values
+                .into_iter()
+                .map(|v| self.write_blob(v, epochs, deletable)),
+        )
+        .await?;
+
+        Ok(blobs)
+    }
+
+    pub async fn read_blob(&self, id: &str) -> anyhow::Result<Blob> {
+        let url = self.aggregator.join(&format!("v1/blobs/{}", id))?;
+
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .context("Failed to reach Walrus aggregator")?;
+        let response = check_status(response).await?;
+
+        let blob = crate::read_stream(response).await?;
+
+        Ok(Blob {
+            blob,
+            blob_id: id.to_string(),
+        })
+    }
+
+    /// Bytes `range` of a blob, with an HTTP range request. Aggregators that
+    /// ignore the range send the whole blob, which is cut down here.
+    pub async fn read_blob_range(&self, id: &str, range: Range<u64>) -> anyhow::Result<Vec<u8>> {
+        if range.start >= range.end {
+            return Ok(Vec::new());
+        }
+        let url = self.aggregator.join(&format!("v1/blobs/{}", id))?;
+
+        let response = self
+            .http
+            .get(url)
+            .header(
+                reqwest::header::RANGE,
+                format!("bytes={}-{}", range.start, range.end - 1),
+            )
+            .send()
+            .await
+            .context("Failed to reach Walrus aggregator")?;
+        let response = check_status(response).await?;
+        let partial = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+
+        let data = crate::read_stream(response).await?;
+        if partial {
+            return Ok(data);
+        }
+        crate::store::slice_range(&data, range)
+    }
+}
+
+async fn check_status(response: reqwest::Response) -> anyhow::Result<reqwest::Response> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(anyhow!("Walrus request failed ({}): {}", status, body));
+    }
+    Ok(response)
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StoreResponse {
+    newly_created: Option<NewlyCreated>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NewlyCreated {
+    blob_object: CreatedBlob,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatedBlob {
+    blob_id: String,
+    id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        extract::{Path, Query, State},
+        routing::{get, put},
+        Json, Router,
+    };
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    type Blobs = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+    async fn store(
+        State(blobs): State<Blobs>,
+        Query(params): Query<HashMap<String, String>>,
+        body: Bytes,
+    ) -> Json<serde_json::Value> {
+        let mut blobs = blobs.lock().unwrap();
+        let blob_id = format!("blob{}", blobs.len());
+        blobs.insert(blob_id.clone(), body.to_vec());
+        let id = format!("0x{:064x}", blobs.len());
+        Json(serde_json::json!({
+            "newlyCreated": {
+                "blobObject": {
+                    "id": id,
+                    "blobId": blob_id,
+                    "registeredEpoch": 1,
+                    "certifiedEpoch": 1,
+                    "size": body.len(),
+                    "encodingType": "RS2",
+                    "deletable": params.contains_key("deletable"),
+                    "storage": {
+                        "id": id,
+                        "startEpoch": 1,
+                        "endEpoch": 1 + params["epochs"].parse::<u32>().unwrap(),
+                        "storageSize": body.len(),
+                    },
+                },
+                "resourceOperation": {
+                    "registerFromScratch": { "encodedLength": body.len(), "epochsAhead": 1 }
+                },
+                "cost": 100,
+            }
+        }))
+    }
+
+    async fn read(
+        State(blobs): State<Blobs>,
+        Path(id): Path<String>,
+    ) -> Result<Vec<u8>, axum::http::StatusCode> {
+        blobs
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or(axum::http::StatusCode::NOT_FOUND)
+    }
+
+    async fn spawn_walrus() -> WalrusClient {
+        let app = Router::new()
+            .route("/v1/blobs", put(store))
+            .route("/v1/blobs/{id}", get(read))
+            .with_state(Blobs::default());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        WalrusClient::new(url.clone(), url)
+    }
+
+    #[tokio::test]
+    async fn test_write_and_read_blobs() {
+        let client = spawn_walrus().await;
+
+        let blobs = client
+            .write_blobs(
+                vec![Bytes::from_static(b"first"), Bytes::from_static(b"second")],
+                4,
+                true,
+            )
+            .await
+            .unwrap();
+        assert_eq!(blobs.len(), 2);
+
+        let first = client.read_blob(&blobs[0].blob_id).await.unwrap();
+        let second = client.read_blob(&blobs[1].blob_id).await.unwrap();
+        assert_eq!(first.blob, b"first");
+        assert_eq!(second.blob, b"second");
+        assert_ne!(blobs[0].object_address, blobs[1].object_address);
+    }
+
+    #[tokio::test]
+    async fn test_read_blob_range() {
+        let client = spawn_walrus().await;
+        let blob = client
+            .write_blob(Bytes::from_static(b"0123456789"), 1, true)
+            .await
+            .unwrap();
+        let part = client.read_blob_range(&blob.blob_id, 2..5).await.unwrap();
+        assert_eq!(part, b"234");
+        assert!(client.read_blob_range(&blob.blob_id, 8..12).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_missing_blob() {
+        let client = spawn_walrus().await;
+        assert!(client.read_blob("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_only_client() {
+        let client = spawn_walrus().await;
+        let blob = client
+            .write_blob(Bytes::from_static(b"data"), 1, true)
+            .await
+            .unwrap();
+
+        let reader = WalrusClient::read_only(client.aggregator.clone());
+        assert_eq!(reader.read_blob(&blob.blob_id).await.unwrap().blob, b"data");
+        assert!(reader
+            .write_blob(Bytes::from_static(b"data"), 1, true)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_storage_estimate() {
+        let price = StoragePrice {
+            storage_per_mib_epoch: 10,
+            write_per_mib: 3,
+        };
+        // 1 MiB encodes to 69 MiB with the metadata overhead
+        assert_eq!(price.estimate(&[MIB], 2), 69 * 23);
+        assert_eq!(
+            price.estimate(&[MIB, 0], 2),
+            price.estimate(&[MIB], 2) + 64 * 23
+        );
+        assert!(price.estimate(&[MIB], 4) > price.estimate(&[MIB], 2));
+    }
+
+    #[test]
+    fn test_blob_object() {
+        let blob_id = primitive_types::U256::from(123_456_789u64);
+        let bts = bcs::to_bytes(&(
+            Address::new([1; 32]),
+            3u32,
+            blob_id.to_little_endian(),
+            1_000u64,
+            1u8,
+            Some(3u32),
+            (Address::new([2; 32]), 3u32, 10u32, 5_000u64),
+            true,
+        ))
+        .unwrap();
+
+        let blob: BlobObject = bcs::from_bytes(&bts).unwrap();
+        assert_eq!(blob.storage.end_epoch, 10);
+        assert!(blob.deletable);
+        assert_eq!(
+            blob.blob_id(),
+            crate::ffi::parse_u256_blob_id("123456789").unwrap()
+        );
+    }
+}
@@ -17,6 +17,7 @@ fun test_drop() {
     let allocation = 813000000000;
 
     let res = drop::verify_sender_proof(
+        1,
         root,
         &proof,
         &sender,
@@ -25,3 +26,46 @@ fun test_drop() {
     );
     assert!(res);
 }
+
+#[test]
+fun test_multi_proof() {
+    // Wallets @0x1 to @0x5 with allocations 10 to 50, proving leaves 1, 2 and 4
+    let root = x"fb598ee59b7af97e1ce9fc51afbd3b938420901fa3f24027ce5d810d06fe11c8";
+    let proof = vector[
+        x"c5e6ea8d59dc94b456a54e9efbcb63fdbb6b2efd51255a36887577b649fd6dbb",
+        x"f4ebba3a25503c3cc1f92b96a21e15fef5373b9a7564fbaca4461f7bb268d7ff",
+    ];
+    let leaves = vector[
+        drop::hash_address_w_allocation(&@0x2, 20),
+        drop::hash_address_w_allocation(&@0x3, 30),
+        drop::hash_address_w_allocation(&@0x5, 50),
+    ];
+
+    assert!(drop::verify_multi_proof(1, &proof, root, leaves, vector[1, 2, 4], 5));
+    assert!(!drop::verify_multi_proof(1, &proof, root, leaves, vector[1, 2, 3], 5));
+    assert!(!drop::verify_multi_proof(1, &proof, root, leaves, vector[1, 2, 4], 6));
+}
+
+#[test]
+fun test_tree_v2() {
+    // The same wallets as `test_multi_proof`, hashed with leaf and node tags
+    let root = x"294d5f593ac9bd4a81c966da94536849a0258e1314e21a00f07d87adef387708";
+    let proof = vector[
+        x"2ac17acfe6afbe4f60e22ee1f97e443613fdef577bd26bb33a4d827aa15d9561",
+        x"1923cdfb47896600ab5616b3299b28824bb7d52df4492ff5a8a435b72a7c87a6",
+        x"9d9e1edd74f7dcbdfe5dc2986a8d3eb7811a637e40e103fa31d31c245b382e70",
+    ];
+    assert!(drop::verify_sender_proof(2, root, &proof, &@0x4, 40, 3));
+    assert!(!drop::verify_sender_proof(1, root, &proof, &@0x4, 40, 3));
+
+    let proof = vector[
+        x"516c5381cc4e8d8328deeb655f90ac7c5134fbf3d7de4e184841532b35e393bf",
+        x"bb200954edd4e1dd4604695c2a82c6418b95b1d67537e4df88fb2a618c8f5972",
+    ];
+    let leaves = vector[
+        drop::hash_leaf(2, &@0x2, 20),
+        drop::hash_leaf(2, &@0x3, 30),
+        drop::hash_leaf(2, &@0x5, 50),
+    ];
+    assert!(drop::verify_multi_proof(2, &proof, root, leaves, vector[1, 2, 4], 5));
+}